@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+// How long to wait after the first failure. Every failure in a row doubles the wait.
+const INITIAL_DELAY: Duration = Duration::from_secs(1);
+
+const MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+
+// Start counting from scratch if nothing failed for this long.
+const RESET_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// Spaces out the retries of something that keeps failing, like a window on a broken monitor, so
+/// that we don’t retry it in a tight loop.
+pub struct Backoff<K> {
+    failures: HashMap<K, Failures>,
+}
+
+struct Failures {
+    count: u32,
+    last_failure: Instant,
+    retry_at: Instant,
+}
+
+impl<K: Eq + Hash> Backoff<K> {
+    pub fn new() -> Self {
+        Self {
+            failures: HashMap::new(),
+        }
+    }
+
+    /// Record a failure. Returns how many times it has failed in a row.
+    pub fn fail(&mut self, key: K, now: Instant) -> u32 {
+        let failures = self.failures.entry(key).or_insert(Failures {
+            count: 0,
+            last_failure: now,
+            retry_at: now,
+        });
+
+        if now.saturating_duration_since(failures.last_failure) > RESET_AFTER {
+            failures.count = 0;
+        }
+
+        failures.count = failures.count.saturating_add(1);
+        failures.last_failure = now;
+        failures.retry_at = now + delay(failures.count);
+
+        failures.count
+    }
+
+    /// Forget the failures, once it has been working for a while.
+    pub fn succeed(&mut self, key: &K) {
+        self.failures.remove(key);
+    }
+
+    /// Returns `true` if it failed recently, and we should wait before trying again.
+    pub fn is_waiting(&self, key: &K, now: Instant) -> bool {
+        self.failures
+            .get(key)
+            .is_some_and(|failures| now < failures.retry_at)
+    }
+}
+
+impl<K: Eq + Hash> Default for Backoff<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn delay(failures: u32) -> Duration {
+    INITIAL_DELAY
+        .saturating_mul(1 << failures.saturating_sub(1).min(16))
+        .min(MAX_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_does_not_wait_before_the_first_failure() {
+        let backoff = Backoff::new();
+
+        assert!(!backoff.is_waiting(&0, Instant::now()));
+    }
+
+    #[test]
+    fn it_doubles_the_wait_after_every_failure() {
+        let mut backoff = Backoff::new();
+        let mut now = Instant::now();
+
+        for (failures, expected_delay) in [(1, 1), (2, 2), (3, 4), (4, 8)] {
+            assert_eq!(backoff.fail(0, now), failures);
+
+            let delay = Duration::from_secs(expected_delay);
+            assert!(backoff.is_waiting(&0, now + delay - Duration::from_millis(1)));
+            assert!(!backoff.is_waiting(&0, now + delay));
+
+            now += delay;
+        }
+    }
+
+    #[test]
+    fn it_caps_the_wait() {
+        let mut backoff = Backoff::new();
+        let now = Instant::now();
+
+        for _ in 0..100 {
+            backoff.fail(0, now);
+        }
+
+        assert!(!backoff.is_waiting(&0, now + MAX_DELAY));
+    }
+
+    #[test]
+    fn it_tracks_each_key_separately() {
+        let mut backoff = Backoff::new();
+        let now = Instant::now();
+
+        backoff.fail(0, now);
+
+        assert!(backoff.is_waiting(&0, now));
+        assert!(!backoff.is_waiting(&1, now));
+    }
+
+    #[test]
+    fn it_starts_over_after_a_success() {
+        let mut backoff = Backoff::new();
+        let now = Instant::now();
+
+        backoff.fail(0, now);
+        backoff.fail(0, now);
+        backoff.succeed(&0);
+
+        assert!(!backoff.is_waiting(&0, now));
+        assert_eq!(backoff.fail(0, now), 1);
+    }
+
+    #[test]
+    fn it_starts_over_after_a_long_time_without_failures() {
+        let mut backoff = Backoff::new();
+        let now = Instant::now();
+
+        backoff.fail(0, now);
+        backoff.fail(0, now);
+
+        assert_eq!(backoff.fail(0, now + RESET_AFTER * 2), 1);
+    }
+}
@@ -0,0 +1,297 @@
+use crate::cli::{BenchmarkOptions, DEFAULT_WINDOW_SIZE};
+use crate::clock::Clock;
+use crate::config::{ClockKind, ClockSettings, ColorMode, Config};
+use crate::gl_context::HumanConfig;
+use crate::gpu_timer::GpuTimer;
+use crate::{Instance, Swapchain};
+
+use std::fs;
+use std::time::{Duration, Instant};
+
+use glow as GL;
+use glow::HasContext;
+use glutin::context::PossiblyCurrentContextGlSurfaceAccessor;
+use glutin::prelude::GlSurface;
+use serde::Serialize;
+
+// Every run animates the same fluid, so the results only depend on the hardware and the rendering
+// settings.
+const SEED: &str = "benchmark";
+const FPS: u32 = 60;
+
+// Drop the first frames, while the driver compiles shaders and the clocks ramp up.
+const WARM_UP: Duration = Duration::from_secs(2);
+
+/// Draw a fixed scenario on every surface as fast as possible, then report the CPU and GPU frame
+/// times.
+pub fn run(
+    sdl_context: &sdl2::Sdl,
+    video_subsystem: &sdl2::VideoSubsystem,
+    mut config: Config,
+    options: &BenchmarkOptions,
+) -> Result<(), String> {
+    set_up_scenario(&mut config);
+
+    let surfaces = crate::plan_surfaces(video_subsystem, &config);
+    let mut instances = surfaces
+        .iter()
+        // Don’t trap the mouse in the windows.
+        .map(|surface| crate::new_instance(video_subsystem, &config, surface, false))
+        .collect::<Result<Vec<Instance>, String>>()?;
+
+    if instances.is_empty() {
+        instances.push(crate::new_windowed_instance(
+            video_subsystem,
+            &config,
+            DEFAULT_WINDOW_SIZE,
+        )?);
+    }
+
+    for instance in instances.iter_mut() {
+        prepare(instance)?;
+        instance.window.show();
+    }
+
+    let mut event_pump = sdl_context.event_pump()?;
+    let mut clock = Clock::from_settings(&config.clock);
+    let mut cpu_times = vec![Vec::new(); instances.len()];
+    let mut warm_up_frames = 0;
+    let mut measured_frames = 0;
+
+    let started_at = Instant::now();
+    let measured_from = started_at + WARM_UP;
+    // When the first measured frame started, which is when the last warm-up frame was presented
+    let mut first_frame_at = None;
+
+    while Instant::now() < measured_from + options.duration {
+        for event in event_pump.poll_iter() {
+            use sdl2::event::Event;
+            use sdl2::keyboard::Keycode;
+
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => return Err("The benchmark was cancelled.".to_string()),
+                _ => (),
+            }
+        }
+
+        let now = Instant::now();
+        if now < measured_from {
+            warm_up_frames += 1;
+        } else {
+            first_frame_at.get_or_insert(now);
+            measured_frames += 1;
+        }
+
+        let timestamp = clock.tick();
+
+        for (instance, cpu_times) in instances.iter_mut().zip(cpu_times.iter_mut()) {
+            let frame_start = Instant::now();
+            instance.draw(timestamp, 0.0, 1.0)?;
+            cpu_times.push(frame_start.elapsed());
+
+            // The context is still current from drawing the frame.
+            if let Some(gpu_timer) = &mut instance.gpu_timer {
+                gpu_timer.collect(&instance.gl_context.gl, false);
+            }
+        }
+    }
+
+    // Every window presents once per loop, one after the other, so they all share the frame
+    // rate. Count the time between presented frames, not just the time spent drawing, so that
+    // waiting on the driver and the other windows shows up too.
+    let elapsed = first_frame_at.map_or(Duration::ZERO, |at| at.elapsed());
+
+    let report = Report {
+        duration_s: options.duration.as_secs_f64(),
+        seed: SEED.to_string(),
+        frames: measured_frames,
+        average_fps: measured_frames as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+        surfaces: instances
+            .iter_mut()
+            .zip(cpu_times.iter())
+            .map(|(instance, cpu_times)| {
+                SurfaceReport::new(instance, &cpu_times[warm_up_frames..], warm_up_frames)
+            })
+            .collect::<Result<Vec<SurfaceReport>, String>>()?,
+    };
+
+    for instance in instances.iter_mut() {
+        if let Some(gpu_timer) = instance.gpu_timer.take() {
+            gpu_timer.delete(&instance.gl_context.gl);
+        }
+    }
+
+    let json = serde_json::to_string_pretty(&report).map_err(|err| err.to_string())?;
+    fs::write(&options.output, &json).map_err(|err| {
+        format!(
+            "Failed to write the benchmark report to {}: {}",
+            options.output.display(),
+            err
+        )
+    })?;
+
+    println!("{json}");
+    println!("Wrote the report to {}", options.output.display());
+
+    Ok(())
+}
+
+// Pin everything that changes what’s drawn, but keep the rendering settings, since comparing those
+// is half the point.
+fn set_up_scenario(config: &mut Config) {
+    config.flux.color_mode = ColorMode::default();
+    config.flux.seed = Some(SEED.to_string());
+    config.clock = ClockSettings {
+        kind: ClockKind::FixedStep,
+        fps: FPS,
+        ..Default::default()
+    };
+    config.fade.fade_in_ms = 0;
+    config.fade.fade_out_ms = 0;
+    config.fade.dissolve_desktop = false;
+    // A changing quality would make the frame times meaningless.
+    config.rendering.adaptive_quality = false;
+    config.kiosk.enabled = false;
+}
+
+// Turn off vsync, so that we measure how fast we can draw, not the refresh rate, and start timing
+// the GPU.
+fn prepare(instance: &mut Instance) -> Result<(), String> {
+    use glutin::surface::SwapInterval;
+
+    instance
+        .gl_context
+        .context
+        .make_current(&instance.gl_context.surface)
+        .map_err(|err| err.to_string())?;
+
+    match &mut instance.swapchain {
+        Swapchain::Gl { vsync } => match instance
+            .gl_context
+            .surface
+            .set_swap_interval(&instance.gl_context.context, SwapInterval::DontWait)
+        {
+            Ok(()) => *vsync = false,
+            Err(err) => log::warn!("Failed to disable vsync: {err:?}"),
+        },
+
+        #[cfg(windows)]
+        Swapchain::Dxgi(dxgi_interop) => dxgi_interop.vsync = false,
+    }
+
+    instance.gpu_timer = GpuTimer::new(&instance.gl_context.gl);
+    if instance.gpu_timer.is_none() {
+        log::warn!("Timer queries aren’t supported. Skipping the GPU frame times.");
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Report {
+    duration_s: f64,
+    seed: String,
+    frames: usize,
+    average_fps: f64,
+    surfaces: Vec<SurfaceReport>,
+}
+
+#[derive(Serialize)]
+struct SurfaceReport {
+    width: u32,
+    height: u32,
+    render_scale: f32,
+    blackout: bool,
+    gl_version: String,
+    gl_vendor: String,
+    gl_renderer: String,
+    gl_config: HumanConfig,
+    swapchain: &'static str,
+    vsync: bool,
+    cpu_frame_ms: Option<Percentiles>,
+    gpu_frame_ms: Option<Percentiles>,
+}
+
+impl SurfaceReport {
+    fn new(
+        instance: &mut Instance,
+        cpu_times: &[Duration],
+        warm_up_frames: usize,
+    ) -> Result<Self, String> {
+        instance
+            .gl_context
+            .context
+            .make_current(&instance.gl_context.surface)
+            .map_err(|err| err.to_string())?;
+
+        let gl = &instance.gl_context.gl;
+        let gpu_frame_ms = instance.gpu_timer.as_mut().and_then(|gpu_timer| {
+            gpu_timer.collect(gl, true);
+            gpu_timer
+                .samples()
+                .get(warm_up_frames..)
+                .and_then(Percentiles::new)
+        });
+
+        Ok(Self {
+            width: instance.surface.size.width,
+            height: instance.surface.size.height,
+            render_scale: instance.effective_render_scale(),
+            blackout: instance.surface.blackout,
+            gl_version: unsafe { gl.get_parameter_string(GL::VERSION) },
+            gl_vendor: unsafe { gl.get_parameter_string(GL::VENDOR) },
+            gl_renderer: unsafe { gl.get_parameter_string(GL::RENDERER) },
+            gl_config: instance.gl_context.config().clone(),
+            swapchain: instance.swapchain_name(),
+            vsync: instance.has_vsync(),
+            cpu_frame_ms: Percentiles::new(cpu_times),
+            gpu_frame_ms,
+        })
+    }
+}
+
+/// Frame time statistics in milliseconds.
+#[derive(Serialize)]
+struct Percentiles {
+    mean: f64,
+    min: f64,
+    p50: f64,
+    p90: f64,
+    p95: f64,
+    p99: f64,
+    max: f64,
+}
+
+impl Percentiles {
+    fn new(samples: &[Duration]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let mut samples = samples
+            .iter()
+            .map(|sample| sample.as_secs_f64() * 1000.0)
+            .collect::<Vec<f64>>();
+        samples.sort_by(f64::total_cmp);
+
+        // Nearest rank
+        let percentile = |p: f64| {
+            let rank = (p * samples.len() as f64).ceil() as usize;
+            samples[rank.clamp(1, samples.len()) - 1]
+        };
+
+        Some(Self {
+            mean: samples.iter().sum::<f64>() / samples.len() as f64,
+            min: samples[0],
+            p50: percentile(0.5),
+            p90: percentile(0.9),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            max: samples[samples.len() - 1],
+        })
+    }
+}
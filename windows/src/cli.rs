@@ -1,5 +1,11 @@
+use crate::config::{ClockKind, ClockSettings, Config};
+
+use flux::settings::ColorPreset;
 use raw_window_handle::RawWindowHandle;
 use std::ffi::c_void;
+use std::path::PathBuf;
+use std::time::Duration;
+use winit::dpi::PhysicalSize;
 
 #[cfg(windows)]
 use windows::Win32::System::LibraryLoader::GetModuleHandleW;
@@ -8,11 +14,120 @@ use windows::Win32::System::LibraryLoader::GetModuleHandleW;
 pub enum Mode {
     Preview(RawWindowHandle),
     Screensaver,
+    Kiosk,
+    Window(PhysicalSize<u32>),
+    Record(RenderOptions),
+    Benchmark(BenchmarkOptions),
+    Diagnostics(DiagnosticsOptions),
     Settings,
+    ListMonitors,
+}
+
+/// What to render without a window, and where to write the frames.
+#[derive(Debug, PartialEq)]
+pub struct RenderOptions {
+    pub size: PhysicalSize<u32>,
+    pub fps: u32,
+    pub frames: u32,
+    pub preset: Option<ColorPreset>,
+    pub output: PathBuf,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            size: DEFAULT_WINDOW_SIZE,
+            fps: 60,
+            frames: 60,
+            preset: None,
+            output: PathBuf::from("recording"),
+        }
+    }
+}
+
+/// How long to run the benchmark, and where to write the report.
+#[derive(Debug, PartialEq)]
+pub struct BenchmarkOptions {
+    pub duration: Duration,
+    pub output: PathBuf,
+}
+
+/// Where to write the diagnostics report.
+#[derive(Debug, PartialEq)]
+pub struct DiagnosticsOptions {
+    pub output: PathBuf,
+}
+
+/// The size of windows and recordings that don’t cover a monitor, like `--window` without a
+/// size, or the benchmark and diagnostics when every monitor is excluded in the settings.
+pub const DEFAULT_WINDOW_SIZE: PhysicalSize<u32> = PhysicalSize::new(1280, 720);
+
+/// Settings that can be overridden from the command line in any mode, to reproduce a run.
+///
+/// --seed SEED -> seed the simulation.
+///
+/// --clock realtime|fixed[:FPS]|scaled:FACTOR -> how time advances in the simulation.
+#[derive(Debug, Default, PartialEq)]
+pub struct Overrides {
+    pub seed: Option<String>,
+    pub clock: Option<ClockSettings>,
+}
+
+impl Overrides {
+    pub fn apply(self, config: &mut Config) {
+        if let Some(seed) = self.seed {
+            config.flux.seed = Some(seed);
+        }
+
+        if let Some(clock) = self.clock {
+            config.clock = clock;
+        }
+    }
+}
+
+const OVERRIDES: [&str; 2] = ["--seed", "--clock"];
+
+pub fn read_overrides() -> Result<Overrides, String> {
+    let mut overrides = Overrides::default();
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        if !OVERRIDES.contains(&arg.as_str()) {
+            continue;
+        }
+
+        let value = args
+            .next()
+            .ok_or_else(|| format!("The option {} needs a value.", arg))?;
+
+        match arg.as_str() {
+            "--seed" => overrides.seed = Some(value),
+            "--clock" => overrides.clock = Some(parse_clock(&value)?),
+            _ => unreachable!(),
+        }
+    }
+
+    Ok(overrides)
+}
+
+// The arguments after the program name, without the overrides.
+fn args() -> Vec<String> {
+    let mut args = Vec::new();
+    let mut all_args = std::env::args().skip(1);
+
+    while let Some(arg) = all_args.next() {
+        if OVERRIDES.contains(&arg.as_str()) {
+            all_args.next();
+        } else {
+            args.push(arg);
+        }
+    }
+
+    args
 }
 
 pub fn read_flags() -> Result<Mode, String> {
-    match std::env::args().nth(1).as_mut().map(|s| {
+    match args().first_mut().map(|s| {
         s.make_ascii_lowercase();
         s.as_str()
     }) {
@@ -46,22 +161,241 @@ pub fn read_flags() -> Result<Mode, String> {
         //
         // -parenthwnd HWND -> Wallpaper Engine
         Some("/p") | Some("-parenthwnd") => {
-            let handle_ptr = std::env::args()
-                .nth(2)
+            let handle_ptr = args()
+                .get(1)
                 .ok_or("Can't find the window to show the screensaver preview.")?
                 .parse::<usize>()
                 .map_err(|e| format!("Can't parse the window handle: {}", e))?;
 
             let mut handle = raw_window_handle::Win32WindowHandle::empty();
             handle.hwnd = handle_ptr as *mut c_void;
-            handle.hinstance =
-                unsafe { GetModuleHandleW(None).expect("current hinstance") }.0 as *mut _;
+            #[cfg(windows)]
+            {
+                handle.hinstance =
+                    unsafe { GetModuleHandleW(None).expect("current hinstance") }.0 as *mut _;
+            }
 
             Ok(Mode::Preview(RawWindowHandle::Win32(handle)))
         }
 
+        // Print the ids of the available monitors, for use in the `monitors` section of the
+        // settings file.
+        Some("--list-monitors") => Ok(Mode::ListMonitors),
+
+        // Run as ambient signage, on the monitors that aren’t excluded in the `monitors` section of
+        // the settings file. Input is ignored, so stop the process to leave.
+        Some("--kiosk") => Ok(Mode::Kiosk),
+
+        // Run in a regular, resizable window. Handy for trying out settings without installing
+        // the screensaver.
+        //
+        // --window [WxH] -> the initial size of the window, like 1280x720.
+        Some("--window") => {
+            let size = match args().get(1) {
+                None => DEFAULT_WINDOW_SIZE,
+                Some(size) => parse_size(size)?,
+            };
+
+            Ok(Mode::Window(size))
+        }
+
+        // Record a clip without a window or a display server, with a fixed-step clock, so that
+        // the same seed and settings always produce the same frames. Write to a directory for a
+        // PNG sequence, or to a .gif file.
+        //
+        // GIFs count frame delays in hundredths of a second, so they play back at the closest
+        // rate that fits, like 50 fps for --fps 60. Use a PNG sequence for an exact frame rate.
+        //
+        // --record [--size WxH] [--fps N] [--frames N | --duration SECONDS] [--preset NAME]
+        //          [--output DIR|FILE.gif]
+        Some("--record") => parse_render_options(RenderOptions::default()).map(Mode::Record),
+
+        // Run a fixed scenario on every monitor and report how long the frames took on the CPU
+        // and the GPU. Use this to compare GPUs and settings.
+        //
+        // --benchmark [--duration SECONDS] [--output FILE]
+        Some("--benchmark") => {
+            let mut options = BenchmarkOptions {
+                duration: Duration::from_secs(30),
+                output: PathBuf::from("benchmark.json"),
+            };
+            let mut args = args().into_iter().skip(1);
+
+            while let Some(arg) = args.next() {
+                let mut value = || {
+                    args.next()
+                        .ok_or_else(|| format!("The option {} needs a value.", arg))
+                };
+
+                match arg.as_str() {
+                    "--duration" => {
+                        options.duration = value()?
+                            .parse::<f64>()
+                            .ok()
+                            .filter(|seconds| seconds.is_finite() && *seconds > 0.0)
+                            .map(Duration::from_secs_f64)
+                            .ok_or("The duration needs to be a positive number of seconds.")?
+                    }
+                    "--output" => options.output = PathBuf::from(value()?),
+                    _ => return Err(format!("I don’t know what the option {} is.", arg)),
+                }
+            }
+
+            Ok(Mode::Benchmark(options))
+        }
+
+        // Describe the monitors, the windows we’d create, and the OpenGL setup for each of them.
+        // Ask for this file when someone reports a black screen.
+        //
+        // --diagnostics [--output FILE.txt|FILE.json]
+        Some("--diagnostics") => {
+            let mut options = DiagnosticsOptions {
+                output: PathBuf::from("flux-diagnostics.txt"),
+            };
+            let mut args = args().into_iter().skip(1);
+
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--output" => {
+                        options.output = PathBuf::from(
+                            args.next()
+                                .ok_or_else(|| format!("The option {} needs a value.", arg))?,
+                        )
+                    }
+                    _ => return Err(format!("I don’t know what the option {} is.", arg)),
+                }
+            }
+
+            Ok(Mode::Diagnostics(options))
+        }
+
         Some(s) => {
             return Err(format!("I don’t know what the argument {} is.", s));
         }
     }
 }
+
+fn parse_size(size: &str) -> Result<PhysicalSize<u32>, String> {
+    let invalid = || {
+        format!(
+            "Can't parse the window size {}. Use WxH, like 1280x720.",
+            size
+        )
+    };
+
+    let (width, height) = size
+        .to_ascii_lowercase()
+        .split_once('x')
+        .ok_or_else(invalid)
+        .and_then(|(width, height)| {
+            Ok((
+                width.parse::<u32>().map_err(|_| invalid())?,
+                height.parse::<u32>().map_err(|_| invalid())?,
+            ))
+        })?;
+
+    if width == 0 || height == 0 {
+        return Err(invalid());
+    }
+
+    Ok(PhysicalSize::new(width, height))
+}
+
+fn parse_render_options(mut options: RenderOptions) -> Result<RenderOptions, String> {
+    let mut args = args().into_iter().skip(1);
+    let mut duration = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("The option {} needs a value.", arg))
+        };
+
+        match arg.as_str() {
+            "--size" => options.size = parse_size(&value()?)?,
+            "--fps" => {
+                options.fps = value()?
+                    .parse()
+                    .ok()
+                    .filter(|fps| *fps > 0)
+                    .ok_or("The frame rate needs to be a positive number.")?
+            }
+            "--frames" => {
+                options.frames = value()?
+                    .parse()
+                    .map_err(|e| format!("Can't parse the number of frames: {}", e))?
+            }
+            "--duration" => {
+                duration = Some(
+                    value()?
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+                        .ok_or("The duration needs to be a number of seconds.")?,
+                )
+            }
+            "--preset" => options.preset = Some(parse_preset(&value()?)?),
+            "--output" => options.output = PathBuf::from(value()?),
+            _ => return Err(format!("I don’t know what the option {} is.", arg)),
+        }
+    }
+
+    if let Some(seconds) = duration {
+        options.frames = (seconds * options.fps as f64).round() as u32;
+    }
+
+    Ok(options)
+}
+
+fn parse_preset(name: &str) -> Result<ColorPreset, String> {
+    match name.to_ascii_lowercase().as_str() {
+        "original" => Ok(ColorPreset::Original),
+        "plasma" => Ok(ColorPreset::Plasma),
+        "poolside" => Ok(ColorPreset::Poolside),
+        _ => Err(format!(
+            "I don’t know the preset {}. Try original, plasma, or poolside.",
+            name
+        )),
+    }
+}
+
+fn parse_clock(clock: &str) -> Result<ClockSettings, String> {
+    let invalid = || {
+        format!(
+            "I don’t know the clock {}. Try realtime, fixed, fixed:30, or scaled:0.5.",
+            clock
+        )
+    };
+
+    let (kind, value) = match clock.to_ascii_lowercase().split_once(':') {
+        Some((kind, value)) => (kind.to_string(), Some(value.to_string())),
+        None => (clock.to_ascii_lowercase(), None),
+    };
+
+    let mut settings = ClockSettings::default();
+
+    match (kind.as_str(), value) {
+        ("realtime", None) => settings.kind = ClockKind::RealTime,
+        ("fixed", fps) => {
+            settings.kind = ClockKind::FixedStep;
+            if let Some(fps) = fps {
+                settings.fps = fps
+                    .parse()
+                    .ok()
+                    .filter(|fps| *fps > 0)
+                    .ok_or_else(invalid)?;
+            }
+        }
+        ("scaled", Some(scale)) => {
+            settings.kind = ClockKind::Scaled;
+            settings.scale = scale
+                .parse()
+                .ok()
+                .filter(|scale: &f64| scale.is_finite() && *scale > 0.0)
+                .ok_or_else(invalid)?;
+        }
+        _ => return Err(invalid()),
+    }
+
+    Ok(settings)
+}
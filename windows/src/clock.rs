@@ -0,0 +1,323 @@
+use crate::config::{ClockKind, ClockSettings};
+
+use std::time::{Duration, Instant};
+
+// Treat any gap between frames longer than this as a stall, like the machine going to sleep or
+// the process being suspended, rather than time the simulation should catch up on.
+const MAX_FRAME_GAP: Duration = Duration::from_millis(250);
+
+// How far to step the simulation over a stall.
+const STALL_STEP: Duration = Duration::from_micros(16_667);
+
+/// A source of monotonically increasing time.
+pub trait TimeSource {
+    /// The time elapsed since some fixed point in the past.
+    fn now(&mut self) -> Duration;
+}
+
+/// Wall-clock time.
+pub struct RealTime {
+    start: Instant,
+}
+
+impl RealTime {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for RealTime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimeSource for RealTime {
+    fn now(&mut self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Advances by the same step on every reading, so every run sees exactly the same times.
+pub struct FixedStep {
+    step: Duration,
+    elapsed: Duration,
+}
+
+impl FixedStep {
+    pub fn new(fps: u32) -> Self {
+        Self {
+            step: Duration::from_secs_f64(1.0 / fps.max(1) as f64),
+            elapsed: Duration::ZERO,
+        }
+    }
+}
+
+impl TimeSource for FixedStep {
+    fn now(&mut self) -> Duration {
+        let reading = self.elapsed;
+        self.elapsed += self.step;
+        reading
+    }
+}
+
+/// Another time source, with any stall squashed down to a single step.
+///
+/// Only wrap sources that follow the wall clock. A fixed step is never a stall, however long it
+/// is, and a scaled source would turn a normal frame into one.
+pub struct SkipStalls {
+    source: Box<dyn TimeSource>,
+    last_reading: Duration,
+    elapsed: Duration,
+}
+
+impl SkipStalls {
+    pub fn new(mut source: Box<dyn TimeSource>) -> Self {
+        Self {
+            last_reading: source.now(),
+            source,
+            elapsed: Duration::ZERO,
+        }
+    }
+}
+
+impl TimeSource for SkipStalls {
+    fn now(&mut self) -> Duration {
+        let reading = self.source.now();
+        let delta = reading.saturating_sub(self.last_reading);
+        self.last_reading = reading;
+
+        self.elapsed += if delta > MAX_FRAME_GAP {
+            log::debug!(
+                "Clamping a gap of {:.1}ms between frames",
+                delta.as_secs_f64() * 1000.0
+            );
+            STALL_STEP
+        } else {
+            delta
+        };
+
+        self.elapsed
+    }
+}
+
+/// Another time source, sped up or slowed down.
+pub struct Scaled {
+    source: Box<dyn TimeSource>,
+    scale: f64,
+}
+
+impl Scaled {
+    pub fn new(source: Box<dyn TimeSource>, scale: f64) -> Self {
+        Self { source, scale }
+    }
+}
+
+impl TimeSource for Scaled {
+    fn now(&mut self) -> Duration {
+        self.source.now().mul_f64(self.scale)
+    }
+}
+
+/// The time passed to the simulation.
+///
+/// Time stands still while the clock is paused. The wall clock sources skip stalls, so the
+/// simulation doesn’t receive a huge time step after the machine resumes from sleep.
+pub struct Clock {
+    source: Box<dyn TimeSource>,
+    last_reading: Duration,
+    elapsed: Duration,
+    paused: bool,
+}
+
+impl Clock {
+    pub fn new(mut source: Box<dyn TimeSource>) -> Self {
+        Self {
+            last_reading: source.now(),
+            source,
+            elapsed: Duration::ZERO,
+            paused: false,
+        }
+    }
+
+    pub fn from_settings(settings: &ClockSettings) -> Self {
+        let source: Box<dyn TimeSource> = match settings.kind {
+            ClockKind::RealTime => Box::new(SkipStalls::new(Box::new(RealTime::new()))),
+            ClockKind::FixedStep => Box::new(FixedStep::new(settings.fps)),
+            ClockKind::Scaled if settings.scale.is_finite() && settings.scale > 0.0 => {
+                // Look for stalls before scaling, so that a slowed down clock still skips them
+                // and a sped up one doesn’t mistake normal frames for them.
+                let real_time = SkipStalls::new(Box::new(RealTime::new()));
+                Box::new(Scaled::new(Box::new(real_time), settings.scale))
+            }
+            ClockKind::Scaled => {
+                log::warn!(
+                    "Ignoring the invalid time scale {}. Using the real time instead.",
+                    settings.scale
+                );
+                Box::new(SkipStalls::new(Box::new(RealTime::new())))
+            }
+        };
+
+        Self::new(source)
+    }
+
+    /// Advance the clock and return the simulation time in milliseconds.
+    pub fn tick(&mut self) -> f64 {
+        let reading = self.source.now();
+        let delta = reading.saturating_sub(self.last_reading);
+        self.last_reading = reading;
+
+        if !self.paused {
+            self.elapsed += delta;
+        }
+
+        self.timestamp()
+    }
+
+    /// The simulation time in milliseconds, as of the last tick.
+    pub fn timestamp(&self) -> f64 {
+        self.elapsed.as_secs_f64() * 1000.0
+    }
+
+    pub fn pause(&mut self) {
+        if !self.paused {
+            log::debug!("Pausing the simulation");
+            self.paused = true;
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if self.paused {
+            log::debug!("Resuming the simulation");
+            self.paused = false;
+            // Don’t count the time spent paused.
+            self.last_reading = self.source.now();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    // A time source the test moves forward by hand.
+    #[derive(Clone, Default)]
+    struct FakeTime(Rc<Cell<Duration>>);
+
+    impl FakeTime {
+        fn advance(&self, millis: u64) {
+            self.0.set(self.0.get() + Duration::from_millis(millis));
+        }
+    }
+
+    impl TimeSource for FakeTime {
+        fn now(&mut self) -> Duration {
+            self.0.get()
+        }
+    }
+
+    fn fake_clock() -> (Clock, FakeTime) {
+        let time = FakeTime::default();
+        (Clock::new(Box::new(time.clone())), time)
+    }
+
+    #[test]
+    fn it_accumulates_the_time_between_frames() {
+        let (mut clock, time) = fake_clock();
+
+        for _ in 0..3 {
+            time.advance(16);
+            clock.tick();
+        }
+
+        assert_eq!(clock.timestamp(), 48.0);
+    }
+
+    fn fake_clock_without_stalls() -> (Clock, FakeTime) {
+        let time = FakeTime::default();
+        let source = SkipStalls::new(Box::new(time.clone()));
+        (Clock::new(Box::new(source)), time)
+    }
+
+    #[test]
+    fn it_clamps_a_stall_to_a_single_step() {
+        let (mut clock, time) = fake_clock_without_stalls();
+
+        time.advance(16);
+        clock.tick();
+        time.advance(10_000);
+        clock.tick();
+
+        let expected = Duration::from_millis(16) + STALL_STEP;
+        assert_eq!(clock.timestamp(), expected.as_secs_f64() * 1000.0);
+    }
+
+    #[test]
+    fn it_does_not_clamp_gaps_up_to_the_limit() {
+        let (mut clock, time) = fake_clock_without_stalls();
+
+        time.advance(MAX_FRAME_GAP.as_millis() as u64);
+        clock.tick();
+
+        assert_eq!(clock.timestamp(), MAX_FRAME_GAP.as_secs_f64() * 1000.0);
+    }
+
+    #[test]
+    fn it_never_clamps_a_fixed_step() {
+        let mut clock = Clock::new(Box::new(FixedStep::new(1)));
+
+        clock.tick();
+        assert_eq!(clock.tick(), 2000.0);
+    }
+
+    #[test]
+    fn it_does_not_mistake_sped_up_frames_for_stalls() {
+        let time = FakeTime::default();
+        let source = SkipStalls::new(Box::new(time.clone()));
+        let mut clock = Clock::new(Box::new(Scaled::new(Box::new(source), 100.0)));
+
+        time.advance(16);
+        assert_eq!(clock.tick(), 1600.0);
+    }
+
+    #[test]
+    fn it_clamps_a_stall_before_slowing_it_down() {
+        let time = FakeTime::default();
+        let source = SkipStalls::new(Box::new(time.clone()));
+        let mut clock = Clock::new(Box::new(Scaled::new(Box::new(source), 0.01)));
+
+        time.advance(10_000);
+        clock.tick();
+
+        assert_eq!(
+            clock.timestamp(),
+            STALL_STEP.mul_f64(0.01).as_secs_f64() * 1000.0
+        );
+    }
+
+    #[test]
+    fn it_freezes_time_while_paused() {
+        let (mut clock, time) = fake_clock();
+
+        time.advance(16);
+        clock.tick();
+
+        clock.pause();
+        for _ in 0..10 {
+            time.advance(100);
+            assert_eq!(clock.tick(), 16.0);
+        }
+
+        clock.resume();
+        assert_eq!(clock.tick(), 16.0);
+
+        time.advance(16);
+        assert_eq!(clock.tick(), 32.0);
+    }
+}
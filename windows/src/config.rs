@@ -1,4 +1,7 @@
+use crate::surface::Surface;
+
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use std::{fmt, fs, io, path};
 
 #[derive(Deserialize, Serialize, Debug, PartialEq)]
@@ -6,6 +9,18 @@ pub struct Config {
     pub version: semver::Version,
     pub log_level: log::Level,
     pub flux: FluxSettings,
+    #[serde(default)]
+    pub monitors: MonitorSettings,
+    #[serde(default)]
+    pub rendering: RenderSettings,
+    #[serde(default)]
+    pub fade: FadeSettings,
+    #[serde(default)]
+    pub exit: ExitSettings,
+    #[serde(default)]
+    pub kiosk: KioskSettings,
+    #[serde(default)]
+    pub clock: ClockSettings,
 
     // An optional path to the location of this config
     #[serde(skip)]
@@ -19,6 +34,12 @@ impl Default for Config {
             version: semver::Version::parse("0.1.0").unwrap(),
             log_level: log::Level::Warn,
             flux: Default::default(),
+            monitors: Default::default(),
+            rendering: Default::default(),
+            fade: Default::default(),
+            exit: Default::default(),
+            kiosk: Default::default(),
+            clock: Default::default(),
             location: None,
         }
     }
@@ -93,26 +114,322 @@ impl Config {
         }
     }
 
-    pub fn to_settings(&self, wallpaper: Option<path::PathBuf>) -> flux::settings::Settings {
+    // Synchronized monitors need to share a seed. Pick one for this run if the user hasn’t set
+    // one.
+    pub fn resolve_seed(&mut self) {
+        if self.monitors.synchronize && self.flux.seed.is_none() {
+            let nanos = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|duration| duration.as_nanos())
+                .unwrap_or_default();
+            self.flux.seed = Some(nanos.to_string());
+        }
+    }
+
+    pub fn to_settings(&self, surface: &Surface) -> flux::settings::Settings {
         use flux::settings;
 
         let color_mode = match &self.flux.color_mode {
             ColorMode::Preset(preset) => settings::ColorMode::Preset(*preset),
-            ColorMode::DesktopImage => wallpaper.map_or(
+            ColorMode::DesktopImage => surface.wallpaper.clone().map_or(
                 settings::ColorMode::default(),
                 settings::ColorMode::ImageFile,
             ),
         };
-        flux::settings::Settings {
+        let mut settings = flux::settings::Settings {
             color_mode,
+            seed: self.flux.seed.clone(),
             ..Default::default()
+        };
+
+        // The default settings are tuned for landscape screens. On portrait screens, the same
+        // settings stretch the fluid vertically and spread the lines out, so we tighten the view
+        // and the grid to keep a similar density of lines across the shorter side.
+        if surface.is_portrait() {
+            let correction = surface.aspect_ratio().sqrt();
+            settings.view_scale /= correction;
+            settings.grid_spacing =
+                ((settings.grid_spacing as f32 / correction).round() as u32).max(1);
         }
+
+        settings
     }
 }
 
 #[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
 pub struct FluxSettings {
     pub color_mode: ColorMode,
+    /// Seed the random state of the simulation to get the same animation every run.
+    #[serde(default)]
+    pub seed: Option<String>,
+}
+
+/// Monitors are identified by the ids printed with `--list-monitors`. Outside of Windows, the ids
+/// include the position of the monitor, so rearranging the monitors means updating these lists.
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct MonitorSettings {
+    /// Monitors to leave uncovered.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Monitors to cover with a black window instead of the animation.
+    #[serde(default)]
+    pub blackout: Vec<String>,
+    /// Start the same simulation on every monitor, so mirrored screens of the same size line up.
+    ///
+    /// Only surfaces of the same size stay identical. The simulation depends on the size, so
+    /// differently sized monitors drift apart. A window that’s recreated, like after plugging in
+    /// a monitor or a driver reset, starts over and falls out of step with the others.
+    #[serde(default)]
+    pub synchronize: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct RenderSettings {
+    /// Cap the frame rate. Without a cap, the frame rate is only limited when vsync is
+    /// unavailable.
+    pub max_fps: Option<u32>,
+    /// Lower the simulation and line quality on machines that can’t keep up, and raise it again
+    /// when there’s headroom. Needs timer queries, which some GLES drivers lack.
+    pub adaptive_quality: bool,
+    /// Render at a fraction of the screen resolution and upscale the result.
+    pub render_scale: f32,
+    pub surface: SurfacePreferences,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            max_fps: None,
+            adaptive_quality: false,
+            render_scale: 1.0,
+            surface: Default::default(),
+        }
+    }
+}
+
+/// What to ask of the OpenGL framebuffer. Drivers don’t offer every combination, so we pick the
+/// closest config.
+#[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct SurfacePreferences {
+    /// Multisample anti-aliasing samples per pixel. Set to 0 to turn it off.
+    ///
+    /// Has no effect with a render scale below 1 or adaptive quality, or with the DXGI swapchain
+    /// on Windows, which draws into its own buffer without multisampling.
+    pub msaa_samples: u8,
+    /// Convert to sRGB when writing to the framebuffer. This brightens the colors, which Flux
+    /// picks for a linear framebuffer.
+    pub srgb: bool,
+    /// Only accept configs that can blend the window with the desktop, instead of falling back to
+    /// an opaque window.
+    pub require_transparency: bool,
+}
+
+// Below this, the lines turn into a blurry mess.
+pub const MIN_RENDER_SCALE: f32 = 0.25;
+
+impl RenderSettings {
+    pub fn render_scale(&self) -> f32 {
+        if self.render_scale.is_finite() {
+            self.render_scale.clamp(MIN_RENDER_SCALE, 1.0)
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Fade in from black when the screensaver starts, and back to black before it exits.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct FadeSettings {
+    pub fade_in_ms: u64,
+    pub fade_out_ms: u64,
+    /// Start from a capture of the desktop, which dissolves into the fluid, instead of fading in
+    /// from black.
+    pub dissolve_desktop: bool,
+    pub dissolve_ms: u64,
+}
+
+impl Default for FadeSettings {
+    fn default() -> Self {
+        Self {
+            fade_in_ms: 1500,
+            fade_out_ms: 300,
+            dissolve_desktop: false,
+            dissolve_ms: 3000,
+        }
+    }
+}
+
+impl FadeSettings {
+    pub fn fade_in(&self) -> Duration {
+        if self.dissolve_desktop {
+            return Duration::ZERO;
+        }
+
+        Duration::from_millis(self.fade_in_ms)
+    }
+
+    pub fn dissolve(&self) -> Duration {
+        if !self.dissolve_desktop {
+            return Duration::ZERO;
+        }
+
+        Duration::from_millis(self.dissolve_ms)
+    }
+
+    pub fn fade_out(&self) -> Duration {
+        Duration::from_millis(self.fade_out_ms)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.fade_in_ms > 0 || self.fade_out_ms > 0
+    }
+}
+
+#[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ExitTrigger {
+    AnyInput,
+    KeyboardOnly,
+    MouseOnly,
+}
+
+/// What ends the screensaver.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct ExitSettings {
+    /// How far the mouse has to move in a single event before we exit. Higher values tolerate
+    /// more mouse movement.
+    pub mouse_threshold: f64,
+    /// Ignore input for this long after starting.
+    pub grace_period_ms: u64,
+    pub trigger: ExitTrigger,
+    /// Require holding a key or mouse button for this long before we exit. Mouse movement is
+    /// ignored. Set to 0 to exit on the first press.
+    pub hold_to_exit_ms: u64,
+}
+
+impl Default for ExitSettings {
+    fn default() -> Self {
+        Self {
+            mouse_threshold: 10.0,
+            grace_period_ms: 500,
+            trigger: ExitTrigger::AnyInput,
+            hold_to_exit_ms: 0,
+        }
+    }
+}
+
+/// Run as ambient signage: fullscreen on every monitor that isn’t excluded in the `monitors`
+/// settings, ignoring input, until the process is stopped.
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct KioskSettings {
+    pub enabled: bool,
+    /// Keep the display awake between these times of day, instead of letting it sleep.
+    pub awake_hours: Option<AwakeHours>,
+}
+
+/// A range of local times, written as "HH:MM". The range wraps around midnight if the end is
+/// before the start, and covers the whole day if they’re equal.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct AwakeHours {
+    pub start: String,
+    pub end: String,
+}
+
+impl AwakeHours {
+    /// Whether the range includes the given number of minutes since midnight.
+    pub fn contains(&self, minutes: u32) -> Result<bool, String> {
+        let start = parse_time_of_day(&self.start)?;
+        let end = parse_time_of_day(&self.end)?;
+
+        Ok(match start.cmp(&end) {
+            std::cmp::Ordering::Less => start <= minutes && minutes < end,
+            std::cmp::Ordering::Greater => start <= minutes || minutes < end,
+            std::cmp::Ordering::Equal => true,
+        })
+    }
+}
+
+fn parse_time_of_day(time: &str) -> Result<u32, String> {
+    let invalid = || format!("Invalid time of day: {}. Use the HH:MM format.", time);
+
+    let (hours, minutes) = time.split_once(':').ok_or_else(invalid)?;
+    let hours = hours.trim().parse::<u32>().map_err(|_| invalid())?;
+    let minutes = minutes.trim().parse::<u32>().map_err(|_| invalid())?;
+
+    if hours > 23 || minutes > 59 {
+        return Err(invalid());
+    }
+
+    Ok(hours * 60 + minutes)
+}
+
+#[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ClockKind {
+    /// Follow the wall clock.
+    RealTime,
+    /// Advance by the same step every frame, regardless of how long the frame took. Use this to
+    /// reproduce a run exactly.
+    FixedStep,
+    /// Follow the wall clock, sped up or slowed down.
+    Scaled,
+}
+
+/// How time advances in the simulation.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct ClockSettings {
+    pub kind: ClockKind,
+    /// The frame rate a fixed-step clock pretends to run at.
+    pub fps: u32,
+    /// How much faster a scaled clock runs than the wall clock.
+    pub scale: f64,
+}
+
+impl Default for ClockSettings {
+    fn default() -> Self {
+        Self {
+            kind: ClockKind::RealTime,
+            fps: 60,
+            scale: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MonitorPlacement {
+    Animate,
+    Blackout,
+    Exclude,
+}
+
+impl std::fmt::Display for MonitorPlacement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                MonitorPlacement::Animate => "animate",
+                MonitorPlacement::Blackout => "blackout",
+                MonitorPlacement::Exclude => "exclude",
+            }
+        )
+    }
+}
+
+impl MonitorSettings {
+    pub fn placement(&self, monitor_id: &str) -> MonitorPlacement {
+        if self.exclude.iter().any(|id| id == monitor_id) {
+            MonitorPlacement::Exclude
+        } else if self.blackout.iter().any(|id| id == monitor_id) {
+            MonitorPlacement::Blackout
+        } else {
+            MonitorPlacement::Animate
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq)]
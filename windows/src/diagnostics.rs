@@ -0,0 +1,271 @@
+use crate::cli::{DiagnosticsOptions, DEFAULT_WINDOW_SIZE};
+use crate::config::Config;
+use crate::gl_context::HumanConfig;
+use crate::surface::Surface;
+use crate::winit_compat::{HasMonitors, HasRotation, HasStableId};
+use crate::Instance;
+
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use glow as GL;
+use glow::HasContext;
+use serde::Serialize;
+
+/// Collect everything we know about the monitors and the OpenGL setup, and write it to a file
+/// users can attach to a bug report.
+///
+/// The report is JSON if the output ends in `.json`, and plain text otherwise.
+pub fn run(
+    video_subsystem: &sdl2::VideoSubsystem,
+    config: Config,
+    options: &DiagnosticsOptions,
+) -> Result<(), String> {
+    let is_json = options
+        .output
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+
+    // Save the report after every section, so that if creating a window or a context crashes the
+    // process, we still have everything up to that point.
+    let mut report = Report {
+        version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        complete: false,
+        monitors: Vec::new(),
+        surfaces: Vec::new(),
+    };
+    save(&report, &options.output, is_json)?;
+
+    report.monitors = video_subsystem
+        .available_monitors()
+        .map(|monitor| {
+            let id = monitor.stable_id();
+            let size = monitor.size();
+            let position = monitor.position();
+
+            MonitorReport {
+                placement: config.monitors.placement(&id).to_string(),
+                id,
+                name: monitor.name().unwrap_or_default(),
+                width: size.width,
+                height: size.height,
+                x: position.x,
+                y: position.y,
+                scale_factor: monitor.scale_factor(),
+                rotation: format!("{:?}", monitor.rotation()),
+            }
+        })
+        .collect();
+    save(&report, &options.output, is_json)?;
+
+    let planned_surfaces = crate::plan_surfaces(video_subsystem, &config);
+
+    if planned_surfaces.is_empty() {
+        report.surfaces.push(SurfaceReport::new(
+            None,
+            crate::new_windowed_instance(video_subsystem, &config, DEFAULT_WINDOW_SIZE),
+        ));
+        save(&report, &options.output, is_json)?;
+    }
+
+    for surface in &planned_surfaces {
+        report.surfaces.push(SurfaceReport::new(
+            Some(surface),
+            // We only create the windows to inspect them, so don’t grab the mouse.
+            crate::new_instance(video_subsystem, &config, surface, false),
+        ));
+        save(&report, &options.output, is_json)?;
+    }
+
+    report.complete = true;
+    save(&report, &options.output, is_json)?;
+
+    println!("{report}");
+    println!("Wrote the diagnostics to {}", options.output.display());
+
+    Ok(())
+}
+
+fn save(report: &Report, path: &Path, is_json: bool) -> Result<(), String> {
+    let contents = if is_json {
+        serde_json::to_string_pretty(report).map_err(|err| err.to_string())?
+    } else {
+        report.to_string()
+    };
+
+    // Sync to disk as well, in case the driver takes down the whole machine.
+    fs::File::create(path)
+        .and_then(|mut file| {
+            file.write_all(contents.as_bytes())?;
+            file.sync_data()
+        })
+        .map_err(|err| {
+            format!(
+                "Failed to write the diagnostics to {}: {}",
+                path.display(),
+                err
+            )
+        })
+}
+
+#[derive(Serialize)]
+struct Report {
+    version: &'static str,
+    os: &'static str,
+    arch: &'static str,
+    // `false` if we crashed or failed while collecting the rest.
+    complete: bool,
+    monitors: Vec<MonitorReport>,
+    surfaces: Vec<SurfaceReport>,
+}
+
+#[derive(Serialize)]
+struct MonitorReport {
+    id: String,
+    name: String,
+    width: u32,
+    height: u32,
+    x: i32,
+    y: i32,
+    scale_factor: f64,
+    rotation: String,
+    placement: String,
+}
+
+#[derive(Serialize)]
+struct SurfaceReport {
+    // `None` for the test window we open when there are no monitors to cover.
+    surface: Option<String>,
+    gl: Option<GlReport>,
+    error: Option<String>,
+}
+
+impl SurfaceReport {
+    fn new(surface: Option<&Surface>, instance: Result<Instance, String>) -> Self {
+        let surface = surface.map(|surface| format!("{:?}", surface));
+
+        match instance {
+            Ok(instance) => Self {
+                surface,
+                gl: Some(GlReport::new(&instance)),
+                error: None,
+            },
+            Err(err) => Self {
+                surface,
+                gl: None,
+                error: Some(err),
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct GlReport {
+    configs: Vec<HumanConfig>,
+    picked_config: usize,
+    version: String,
+    shading_language_version: String,
+    vendor: String,
+    renderer: String,
+    extensions: Vec<String>,
+    swapchain: &'static str,
+    vsync: bool,
+}
+
+impl GlReport {
+    fn new(instance: &Instance) -> Self {
+        // The context is still current from creating the instance.
+        let gl = &instance.gl_context.gl;
+        let mut extensions = gl
+            .supported_extensions()
+            .iter()
+            .cloned()
+            .collect::<Vec<String>>();
+        extensions.sort();
+
+        unsafe {
+            Self {
+                configs: instance.gl_context.configs.clone(),
+                picked_config: instance.gl_context.config_index,
+                version: gl.get_parameter_string(GL::VERSION),
+                shading_language_version: gl.get_parameter_string(GL::SHADING_LANGUAGE_VERSION),
+                vendor: gl.get_parameter_string(GL::VENDOR),
+                renderer: gl.get_parameter_string(GL::RENDERER),
+                extensions,
+                swapchain: instance.swapchain_name(),
+                vsync: instance.has_vsync(),
+            }
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Flux {} on {} ({})", self.version, self.os, self.arch)?;
+
+        if !self.complete {
+            writeln!(f, "Incomplete: stopped while collecting the next section")?;
+        }
+
+        writeln!(f, "\nMonitors")?;
+        if self.monitors.is_empty() {
+            writeln!(f, "  None found")?;
+        }
+        for monitor in &self.monitors {
+            writeln!(
+                f,
+                "  {}\n    Name: {}\n    Size: {}x{} at ({}, {})\n    Scale factor: {}\n    Rotation: {}\n    Placement: {}",
+                monitor.id,
+                monitor.name,
+                monitor.width,
+                monitor.height,
+                monitor.x,
+                monitor.y,
+                monitor.scale_factor,
+                monitor.rotation,
+                monitor.placement,
+            )?;
+        }
+
+        for (index, surface) in self.surfaces.iter().enumerate() {
+            writeln!(f, "\nSurface #{index}")?;
+            writeln!(
+                f,
+                "  {}",
+                surface.surface.as_deref().unwrap_or("Test window")
+            )?;
+
+            if let Some(error) = &surface.error {
+                writeln!(f, "  Failed: {error}")?;
+            }
+
+            if let Some(gl) = &surface.gl {
+                writeln!(f, "  Version: {}", gl.version)?;
+                writeln!(f, "  Shading language: {}", gl.shading_language_version)?;
+                writeln!(f, "  Vendor: {}", gl.vendor)?;
+                writeln!(f, "  Renderer: {}", gl.renderer)?;
+                writeln!(f, "  Swapchain: {}", gl.swapchain)?;
+                writeln!(f, "  Vsync: {}", gl.vsync)?;
+                writeln!(f, "  Extensions: {}", gl.extensions.join(" "))?;
+
+                for (index, config) in gl.configs.iter().enumerate() {
+                    let picked = if index == gl.picked_config {
+                        " (picked)"
+                    } else {
+                        ""
+                    };
+                    writeln!(f, "  Config #{index}{picked}")?;
+                    for line in config.to_string().lines() {
+                        writeln!(f, "    {line}")?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
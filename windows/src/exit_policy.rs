@@ -0,0 +1,297 @@
+use crate::config::{ExitSettings, ExitTrigger};
+
+use std::time::{Duration, Instant};
+
+use sdl2::event::Event;
+
+/// Input that might end the screensaver.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interaction {
+    Press { keyboard: bool },
+    Release { keyboard: bool },
+    // The largest relative movement along either axis
+    Motion(f64),
+}
+
+impl Interaction {
+    pub fn from_event(event: &Event) -> Option<Self> {
+        match *event {
+            // Ignore key repeats, so that holding a key doesn’t restart the hold.
+            Event::KeyDown { repeat: true, .. } => None,
+            Event::KeyDown { .. } => Some(Self::Press { keyboard: true }),
+            Event::KeyUp { .. } => Some(Self::Release { keyboard: true }),
+            Event::MouseButtonDown { .. } => Some(Self::Press { keyboard: false }),
+            Event::MouseButtonUp { .. } => Some(Self::Release { keyboard: false }),
+            Event::MouseMotion { xrel, yrel, .. } => {
+                Some(Self::Motion(f64::max(xrel.abs() as f64, yrel.abs() as f64)))
+            }
+            _ => None,
+        }
+    }
+
+    fn is_keyboard(&self) -> bool {
+        matches!(
+            self,
+            Self::Press { keyboard: true } | Self::Release { keyboard: true }
+        )
+    }
+}
+
+/// Decides which input ends the screensaver.
+pub struct ExitPolicy {
+    mouse_threshold: f64,
+    trigger: ExitTrigger,
+    hold_to_exit: Option<Duration>,
+    ignore_until: Instant,
+    held_since: Option<Instant>,
+}
+
+impl ExitPolicy {
+    pub fn new(settings: &ExitSettings, now: Instant) -> Self {
+        Self {
+            mouse_threshold: settings.mouse_threshold,
+            trigger: settings.trigger,
+            hold_to_exit: Some(Duration::from_millis(settings.hold_to_exit_ms))
+                .filter(|duration| !duration.is_zero()),
+            ignore_until: now + Duration::from_millis(settings.grace_period_ms),
+            held_since: None,
+        }
+    }
+
+    /// Returns `true` if the event should end the screensaver.
+    pub fn handle(&mut self, event: &Event, now: Instant) -> bool {
+        match Interaction::from_event(event) {
+            Some(interaction) => self.handle_interaction(interaction, now),
+            None => false,
+        }
+    }
+
+    pub fn handle_interaction(&mut self, interaction: Interaction, now: Instant) -> bool {
+        // Windows and SDL send a few spurious events, like mouse motion when the cursor is
+        // captured, right after the windows are created.
+        if now < self.ignore_until {
+            return false;
+        }
+
+        let accepted = match self.trigger {
+            ExitTrigger::AnyInput => true,
+            ExitTrigger::KeyboardOnly => interaction.is_keyboard(),
+            ExitTrigger::MouseOnly => !interaction.is_keyboard(),
+        };
+
+        if !accepted {
+            return false;
+        }
+
+        match (interaction, self.hold_to_exit) {
+            (Interaction::Motion(distance), None) => distance > self.mouse_threshold,
+            (Interaction::Press { .. }, None) => true,
+            (Interaction::Release { .. }, None) => false,
+
+            // When holding to exit, only a held key or button counts.
+            (Interaction::Motion(_), Some(_)) => false,
+            (Interaction::Press { .. }, Some(_)) => {
+                self.held_since.get_or_insert(now);
+                false
+            }
+            (Interaction::Release { .. }, Some(_)) => {
+                self.held_since = None;
+                false
+            }
+        }
+    }
+
+    /// Returns `true` once a key or button has been held for long enough to end the screensaver.
+    pub fn poll(&mut self, now: Instant) -> bool {
+        match (self.held_since, self.hold_to_exit) {
+            (Some(held_since), Some(hold_to_exit))
+                if now.saturating_duration_since(held_since) >= hold_to_exit =>
+            {
+                self.held_since = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sdl2::keyboard::{Keycode, Mod, Scancode};
+    use sdl2::mouse::{MouseButton, MouseState};
+
+    fn key_down(repeat: bool) -> Event {
+        Event::KeyDown {
+            timestamp: 0,
+            window_id: 0,
+            keycode: Some(Keycode::Space),
+            scancode: Some(Scancode::Space),
+            keymod: Mod::NOMOD,
+            repeat,
+        }
+    }
+
+    fn key_up() -> Event {
+        Event::KeyUp {
+            timestamp: 0,
+            window_id: 0,
+            keycode: Some(Keycode::Space),
+            scancode: Some(Scancode::Space),
+            keymod: Mod::NOMOD,
+            repeat: false,
+        }
+    }
+
+    fn button_down() -> Event {
+        Event::MouseButtonDown {
+            timestamp: 0,
+            window_id: 0,
+            which: 0,
+            mouse_btn: MouseButton::Left,
+            clicks: 1,
+            x: 0,
+            y: 0,
+        }
+    }
+
+    fn button_up() -> Event {
+        Event::MouseButtonUp {
+            timestamp: 0,
+            window_id: 0,
+            which: 0,
+            mouse_btn: MouseButton::Left,
+            clicks: 1,
+            x: 0,
+            y: 0,
+        }
+    }
+
+    fn motion(xrel: i32, yrel: i32) -> Event {
+        Event::MouseMotion {
+            timestamp: 0,
+            window_id: 0,
+            which: 0,
+            mousestate: MouseState::from_sdl_state(0),
+            x: 0,
+            y: 0,
+            xrel,
+            yrel,
+        }
+    }
+
+    // A policy past its grace period, and the time to feed it events at.
+    fn policy(settings: ExitSettings) -> (ExitPolicy, Instant) {
+        let start = Instant::now();
+        let now = start + Duration::from_millis(settings.grace_period_ms + 1);
+        (ExitPolicy::new(&settings, start), now)
+    }
+
+    #[test]
+    fn it_ignores_motion_below_the_threshold() {
+        let (mut policy, now) = policy(ExitSettings::default());
+
+        assert!(!policy.handle(&motion(3, -10), now));
+        assert!(!policy.handle(&motion(0, 0), now));
+    }
+
+    #[test]
+    fn it_exits_on_motion_above_the_threshold() {
+        let (mut policy, now) = policy(ExitSettings::default());
+
+        assert!(policy.handle(&motion(11, 0), now));
+        assert!(policy.handle(&motion(0, -11), now));
+    }
+
+    #[test]
+    fn it_ignores_input_during_the_grace_period() {
+        let start = Instant::now();
+        let mut policy = ExitPolicy::new(&ExitSettings::default(), start);
+        let during_grace_period = start + Duration::from_millis(100);
+
+        assert!(!policy.handle(&motion(100, 100), during_grace_period));
+        assert!(!policy.handle(&key_down(false), during_grace_period));
+        assert!(!policy.handle(&button_down(), during_grace_period));
+
+        let after_grace_period = start + Duration::from_millis(500);
+        assert!(policy.handle(&key_down(false), after_grace_period));
+    }
+
+    #[test]
+    fn it_exits_on_any_press() {
+        let (mut policy, now) = policy(ExitSettings::default());
+
+        assert!(policy.handle(&key_down(false), now));
+        assert!(policy.handle(&button_down(), now));
+        assert!(!policy.handle(&key_up(), now));
+        assert!(!policy.handle(&button_up(), now));
+    }
+
+    #[test]
+    fn it_only_exits_on_the_keyboard_in_keyboard_only_mode() {
+        let (mut policy, now) = policy(ExitSettings {
+            trigger: ExitTrigger::KeyboardOnly,
+            ..Default::default()
+        });
+
+        assert!(!policy.handle(&motion(100, 100), now));
+        assert!(!policy.handle(&button_down(), now));
+        assert!(policy.handle(&key_down(false), now));
+    }
+
+    #[test]
+    fn it_only_exits_on_the_mouse_in_mouse_only_mode() {
+        let (mut policy, now) = policy(ExitSettings {
+            trigger: ExitTrigger::MouseOnly,
+            ..Default::default()
+        });
+
+        assert!(!policy.handle(&key_down(false), now));
+        assert!(policy.handle(&button_down(), now));
+        assert!(policy.handle(&motion(100, 100), now));
+    }
+
+    #[test]
+    fn it_exits_after_holding_a_key() {
+        let (mut policy, now) = policy(ExitSettings {
+            hold_to_exit_ms: 1000,
+            ..Default::default()
+        });
+
+        assert!(!policy.handle(&key_down(false), now));
+        assert!(!policy.poll(now + Duration::from_millis(999)));
+
+        // Key repeats don’t restart the hold.
+        assert!(!policy.handle(&key_down(true), now + Duration::from_millis(500)));
+        assert!(policy.poll(now + Duration::from_millis(1000)));
+    }
+
+    #[test]
+    fn it_restarts_the_hold_after_a_release() {
+        let (mut policy, now) = policy(ExitSettings {
+            hold_to_exit_ms: 1000,
+            ..Default::default()
+        });
+
+        assert!(!policy.handle(&button_down(), now));
+        assert!(!policy.handle(&button_up(), now + Duration::from_millis(800)));
+        assert!(!policy.poll(now + Duration::from_millis(1000)));
+
+        let pressed_again = now + Duration::from_millis(1200);
+        assert!(!policy.handle(&button_down(), pressed_again));
+        assert!(!policy.poll(pressed_again + Duration::from_millis(999)));
+        assert!(policy.poll(pressed_again + Duration::from_millis(1000)));
+    }
+
+    #[test]
+    fn it_ignores_motion_when_holding_to_exit() {
+        let (mut policy, now) = policy(ExitSettings {
+            hold_to_exit_ms: 1000,
+            ..Default::default()
+        });
+
+        assert!(!policy.handle(&motion(100, 100), now));
+        assert!(!policy.poll(now + Duration::from_secs(10)));
+    }
+}
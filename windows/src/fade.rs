@@ -0,0 +1,342 @@
+use crate::platform::Screenshot;
+
+use std::time::{Duration, Instant};
+
+use glow as GL;
+use glow::HasContext;
+use winit::dpi::PhysicalSize;
+
+// Ignore further input for this long after starting to fade out. A single flick of the mouse
+// produces a burst of events, which shouldn’t count as continued interaction.
+const INTERRUPT_GRACE_PERIOD: Duration = Duration::from_millis(100);
+
+/// Tracks the fade in from black when the screensaver starts and the fade out before it exits.
+pub struct Fader {
+    fade_in: Duration,
+    fade_out: Duration,
+    dissolve: Duration,
+    started_at: Instant,
+    fade_out_from: Option<(Instant, f32)>,
+}
+
+impl Fader {
+    pub fn new(fade_in: Duration, fade_out: Duration, dissolve: Duration) -> Self {
+        Self {
+            fade_in,
+            fade_out,
+            dissolve,
+            started_at: Instant::now(),
+            fade_out_from: None,
+        }
+    }
+
+    /// The opacity of the black overlay, from 0 (invisible) to 1 (fully black).
+    pub fn opacity(&self, now: Instant) -> f32 {
+        match self.fade_out_from {
+            None => 1.0 - progress(now.saturating_duration_since(self.started_at), self.fade_in),
+            Some((fade_out_start, opacity)) => {
+                let elapsed = now.saturating_duration_since(fade_out_start);
+                opacity + (1.0 - opacity) * progress(elapsed, self.fade_out)
+            }
+        }
+    }
+
+    /// Start fading out. Returns `true` if we should exit right away instead, because the user
+    /// kept interacting after the fade out started.
+    pub fn fade_out(&mut self, now: Instant) -> bool {
+        match self.fade_out_from {
+            None => {
+                self.fade_out_from = Some((now, self.opacity(now)));
+                self.fade_out.is_zero()
+            }
+            Some((fade_out_start, _)) => {
+                now.saturating_duration_since(fade_out_start) > INTERRUPT_GRACE_PERIOD
+            }
+        }
+    }
+
+    pub fn is_faded_out(&self, now: Instant) -> bool {
+        self.fade_out_from.is_some() && self.opacity(now) >= 1.0
+    }
+
+    /// How far the desktop capture has dissolved into the simulation, from 0 to 1.
+    pub fn dissolve_progress(&self, now: Instant) -> f32 {
+        progress(
+            now.saturating_duration_since(self.started_at),
+            self.dissolve,
+        )
+    }
+}
+
+fn progress(elapsed: Duration, duration: Duration) -> f32 {
+    if duration.is_zero() {
+        return 1.0;
+    }
+
+    (elapsed.as_secs_f32() / duration.as_secs_f32()).clamp(0.0, 1.0)
+}
+
+/// A full-screen pass that blends black over whatever has been drawn.
+pub struct Overlay {
+    program: GL::NativeProgram,
+    vertex_array: GL::NativeVertexArray,
+    color: Option<GL::NativeUniformLocation>,
+}
+
+// A single triangle that covers the whole screen.
+const VERTEX_SHADER: &str = r#"
+const vec2 positions[3] = vec2[3](vec2(-1.0, -1.0), vec2(3.0, -1.0), vec2(-1.0, 3.0));
+
+void main() {
+    gl_Position = vec4(positions[gl_VertexID], 0.0, 1.0);
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+precision mediump float;
+
+uniform vec4 u_color;
+
+out vec4 fragColor;
+
+void main() {
+    fragColor = u_color;
+}
+"#;
+
+impl Overlay {
+    pub fn new(gl: &glow::Context) -> Result<Self, String> {
+        unsafe {
+            let program = compile_program(gl, VERTEX_SHADER, FRAGMENT_SHADER)?;
+            let color = gl.get_uniform_location(program, "u_color");
+
+            // Core profiles refuse to draw without a vertex array bound, even if it’s empty.
+            let vertex_array = gl.create_vertex_array()?;
+
+            Ok(Self {
+                program,
+                vertex_array,
+                color,
+            })
+        }
+    }
+
+    /// Blend black over `framebuffer`, or the default framebuffer if `None`.
+    pub fn draw(
+        &self,
+        gl: &glow::Context,
+        framebuffer: Option<GL::NativeFramebuffer>,
+        size: PhysicalSize<u32>,
+        opacity: f32,
+    ) {
+        if opacity <= 0.0 {
+            return;
+        }
+
+        unsafe {
+            let blend_enabled = gl.is_enabled(GL::BLEND);
+
+            gl.bind_framebuffer(GL::FRAMEBUFFER, framebuffer);
+            gl.viewport(0, 0, size.width as i32, size.height as i32);
+
+            // Leave the alpha channel alone, so the window stays opaque.
+            gl.enable(GL::BLEND);
+            gl.blend_func_separate(GL::SRC_ALPHA, GL::ONE_MINUS_SRC_ALPHA, GL::ZERO, GL::ONE);
+
+            gl.use_program(Some(self.program));
+            gl.uniform_4_f32(self.color.as_ref(), 0.0, 0.0, 0.0, opacity.min(1.0));
+            gl.bind_vertex_array(Some(self.vertex_array));
+            gl.draw_arrays(GL::TRIANGLES, 0, 3);
+            gl.bind_vertex_array(None);
+            gl.use_program(None);
+
+            if !blend_enabled {
+                gl.disable(GL::BLEND);
+            }
+        }
+    }
+}
+
+unsafe fn compile_program(
+    gl: &glow::Context,
+    vertex_source: &str,
+    fragment_source: &str,
+) -> Result<GL::NativeProgram, String> {
+    // Flux requires either OpenGL 3.3 or GLES 3.0.
+    let version = if gl.version().is_embedded {
+        "#version 300 es"
+    } else {
+        "#version 330 core"
+    };
+
+    let program = gl.create_program()?;
+    let mut shaders = Vec::with_capacity(2);
+
+    for (shader_type, source) in [
+        (GL::VERTEX_SHADER, vertex_source),
+        (GL::FRAGMENT_SHADER, fragment_source),
+    ] {
+        let shader = gl.create_shader(shader_type)?;
+        gl.shader_source(shader, &format!("{version}\n{source}"));
+        gl.compile_shader(shader);
+
+        if !gl.get_shader_compile_status(shader) {
+            let log = gl.get_shader_info_log(shader);
+            gl.delete_shader(shader);
+            gl.delete_program(program);
+            return Err(format!("Failed to compile overlay shader: {log}"));
+        }
+
+        gl.attach_shader(program, shader);
+        shaders.push(shader);
+    }
+
+    gl.link_program(program);
+
+    for shader in shaders {
+        gl.detach_shader(program, shader);
+        gl.delete_shader(shader);
+    }
+
+    if !gl.get_program_link_status(program) {
+        let log = gl.get_program_info_log(program);
+        gl.delete_program(program);
+        return Err(format!("Failed to link overlay shader: {log}"));
+    }
+
+    Ok(program)
+}
+
+/// A capture of the desktop drawn over the simulation, which breaks up in patches to reveal the
+/// fluid underneath.
+pub struct Dissolve {
+    program: GL::NativeProgram,
+    vertex_array: GL::NativeVertexArray,
+    texture: GL::NativeTexture,
+    image: Option<GL::NativeUniformLocation>,
+    size: Option<GL::NativeUniformLocation>,
+    progress: Option<GL::NativeUniformLocation>,
+}
+
+const DISSOLVE_FRAGMENT_SHADER: &str = r#"
+precision highp float;
+
+uniform sampler2D u_image;
+uniform vec2 u_size;
+uniform float u_progress;
+
+out vec4 fragColor;
+
+float hash(vec2 p) {
+    return fract(sin(dot(p, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+float noise(vec2 p) {
+    vec2 i = floor(p);
+    vec2 f = fract(p);
+    vec2 u = f * f * (3.0 - 2.0 * f);
+
+    return mix(
+        mix(hash(i), hash(i + vec2(1.0, 0.0)), u.x),
+        mix(hash(i + vec2(0.0, 1.0)), hash(i + vec2(1.0, 1.0)), u.x),
+        u.y
+    );
+}
+
+void main() {
+    vec2 uv = gl_FragCoord.xy / u_size;
+    // The capture is stored top to bottom.
+    vec3 color = texture(u_image, vec2(uv.x, 1.0 - uv.y)).rgb;
+
+    // Each pixel fades over a fifth of the dissolve, starting at a time picked by the noise.
+    float start = 0.8 * (0.5 * noise(gl_FragCoord.xy / 64.0) + 0.5 * noise(gl_FragCoord.xy / 16.0));
+    float alpha = 1.0 - smoothstep(start, start + 0.2, u_progress);
+
+    fragColor = vec4(color, alpha);
+}
+"#;
+
+impl Dissolve {
+    pub fn new(gl: &glow::Context, screenshot: &Screenshot) -> Result<Self, String> {
+        unsafe {
+            let program = compile_program(gl, VERTEX_SHADER, DISSOLVE_FRAGMENT_SHADER)?;
+            let image = gl.get_uniform_location(program, "u_image");
+            let size = gl.get_uniform_location(program, "u_size");
+            let progress = gl.get_uniform_location(program, "u_progress");
+            let vertex_array = gl.create_vertex_array()?;
+
+            let texture = gl.create_texture()?;
+            gl.bind_texture(GL::TEXTURE_2D, Some(texture));
+            gl.tex_parameter_i32(GL::TEXTURE_2D, GL::TEXTURE_MIN_FILTER, GL::LINEAR as i32);
+            gl.tex_parameter_i32(GL::TEXTURE_2D, GL::TEXTURE_MAG_FILTER, GL::LINEAR as i32);
+            gl.tex_parameter_i32(GL::TEXTURE_2D, GL::TEXTURE_WRAP_S, GL::CLAMP_TO_EDGE as i32);
+            gl.tex_parameter_i32(GL::TEXTURE_2D, GL::TEXTURE_WRAP_T, GL::CLAMP_TO_EDGE as i32);
+            gl.pixel_store_i32(GL::UNPACK_ALIGNMENT, 1);
+            gl.tex_image_2d(
+                GL::TEXTURE_2D,
+                0,
+                GL::RGBA8 as i32,
+                screenshot.size.width as i32,
+                screenshot.size.height as i32,
+                0,
+                GL::RGBA,
+                GL::UNSIGNED_BYTE,
+                Some(&screenshot.pixels),
+            );
+            gl.bind_texture(GL::TEXTURE_2D, None);
+
+            Ok(Self {
+                program,
+                vertex_array,
+                texture,
+                image,
+                size,
+                progress,
+            })
+        }
+    }
+
+    /// Blend what’s left of the capture over `framebuffer`, or the default framebuffer if `None`.
+    pub fn draw(
+        &self,
+        gl: &glow::Context,
+        framebuffer: Option<GL::NativeFramebuffer>,
+        size: PhysicalSize<u32>,
+        progress: f32,
+    ) {
+        unsafe {
+            let blend_enabled = gl.is_enabled(GL::BLEND);
+
+            gl.bind_framebuffer(GL::FRAMEBUFFER, framebuffer);
+            gl.viewport(0, 0, size.width as i32, size.height as i32);
+
+            // Leave the alpha channel alone, so the window stays opaque.
+            gl.enable(GL::BLEND);
+            gl.blend_func_separate(GL::SRC_ALPHA, GL::ONE_MINUS_SRC_ALPHA, GL::ZERO, GL::ONE);
+
+            gl.use_program(Some(self.program));
+            gl.active_texture(GL::TEXTURE0);
+            gl.bind_texture(GL::TEXTURE_2D, Some(self.texture));
+            gl.uniform_1_i32(self.image.as_ref(), 0);
+            gl.uniform_2_f32(self.size.as_ref(), size.width as f32, size.height as f32);
+            gl.uniform_1_f32(self.progress.as_ref(), progress.clamp(0.0, 1.0));
+            gl.bind_vertex_array(Some(self.vertex_array));
+            gl.draw_arrays(GL::TRIANGLES, 0, 3);
+            gl.bind_vertex_array(None);
+            gl.bind_texture(GL::TEXTURE_2D, None);
+            gl.use_program(None);
+
+            if !blend_enabled {
+                gl.disable(GL::BLEND);
+            }
+        }
+    }
+
+    pub fn delete(self, gl: &glow::Context) {
+        unsafe {
+            gl.delete_texture(self.texture);
+            gl.delete_vertex_array(self.vertex_array);
+            gl.delete_program(self.program);
+        }
+    }
+}
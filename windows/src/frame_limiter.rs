@@ -0,0 +1,160 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+// Sleep until this close to the deadline, then spin for the rest. Even with a raised timer
+// resolution, sleeping tends to overshoot by up to a millisecond.
+const SPIN_THRESHOLD: Duration = Duration::from_micros(1500);
+
+// How often to log the frame timing statistics.
+const STATS_LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Paces the render loop to a maximum frame rate.
+///
+/// Without vsync, nothing stops the render loop from drawing frames as fast as it can, pinning a
+/// CPU core and the GPU.
+pub struct FrameLimiter {
+    frame_duration: Option<Duration>,
+    frame_start: Instant,
+    next_frame: Instant,
+    stats: FrameStats,
+
+    #[cfg(windows)]
+    timer_resolution: Option<crate::platform::windows::timer::TimerResolution>,
+}
+
+impl FrameLimiter {
+    pub fn new(max_fps: Option<u32>) -> Self {
+        let now = Instant::now();
+        let mut frame_limiter = Self {
+            frame_duration: None,
+            frame_start: now,
+            next_frame: now,
+            stats: FrameStats::new(now),
+
+            #[cfg(windows)]
+            timer_resolution: None,
+        };
+        frame_limiter.set_max_fps(max_fps);
+
+        frame_limiter
+    }
+
+    pub fn set_max_fps(&mut self, max_fps: Option<u32>) {
+        let frame_duration = max_fps
+            .filter(|fps| *fps > 0)
+            .map(|fps| Duration::from_secs_f64(1.0 / fps as f64));
+
+        if frame_duration == self.frame_duration {
+            return;
+        }
+
+        match max_fps {
+            Some(fps) if frame_duration.is_some() => {
+                log::debug!("Limiting frame rate to {fps} fps")
+            }
+            _ => log::debug!("Frame rate is unlimited"),
+        }
+
+        #[cfg(windows)]
+        {
+            self.timer_resolution = frame_duration
+                .and_then(|_| crate::platform::windows::timer::TimerResolution::new(1));
+        }
+
+        self.frame_duration = frame_duration;
+        self.next_frame = Instant::now();
+    }
+
+    /// Record the time spent on the current frame, and wait until it’s time to start the next
+    /// one.
+    pub fn wait(&mut self) {
+        let frame_end = Instant::now();
+        self.stats.record(frame_end - self.frame_start);
+
+        if let Some(frame_duration) = self.frame_duration {
+            let deadline = self.next_frame;
+
+            if let Some(remaining) = deadline.checked_duration_since(frame_end) {
+                if remaining > SPIN_THRESHOLD {
+                    std::thread::sleep(remaining - SPIN_THRESHOLD);
+                }
+
+                while Instant::now() < deadline {
+                    std::hint::spin_loop();
+                }
+            }
+
+            // Don’t try to catch up on missed frames by rushing the next ones.
+            self.next_frame = deadline.max(frame_end) + frame_duration;
+        }
+
+        self.frame_start = Instant::now();
+
+        if self.stats.elapsed(self.frame_start) > STATS_LOG_INTERVAL {
+            log::debug!("Frame timings: {}", self.stats);
+            self.stats = FrameStats::new(self.frame_start);
+        }
+    }
+}
+
+/// Timings for the frames drawn since the statistics were last reset.
+#[derive(Clone, Debug)]
+pub struct FrameStats {
+    since: Instant,
+    frames: u32,
+    total_frame_time: Duration,
+    min_frame_time: Duration,
+    max_frame_time: Duration,
+}
+
+impl FrameStats {
+    fn new(since: Instant) -> Self {
+        Self {
+            since,
+            frames: 0,
+            total_frame_time: Duration::ZERO,
+            min_frame_time: Duration::MAX,
+            max_frame_time: Duration::ZERO,
+        }
+    }
+
+    fn record(&mut self, frame_time: Duration) {
+        self.frames += 1;
+        self.total_frame_time += frame_time;
+        self.min_frame_time = self.min_frame_time.min(frame_time);
+        self.max_frame_time = self.max_frame_time.max(frame_time);
+    }
+
+    fn elapsed(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.since)
+    }
+
+    /// Frames presented per second, including time spent waiting on the limiter.
+    pub fn fps(&self) -> f64 {
+        self.frames as f64 / self.elapsed(Instant::now()).as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// The average time spent drawing a frame, excluding time spent waiting on the limiter.
+    pub fn average_frame_time(&self) -> Duration {
+        self.total_frame_time
+            .checked_div(self.frames)
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for FrameStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.frames == 0 {
+            return write!(f, "no frames");
+        }
+
+        write!(
+            f,
+            "{:.1} fps, frame time avg {:.2} ms, min {:.2} ms, max {:.2} ms",
+            self.fps(),
+            self.average_frame_time().as_secs_f64() * 1000.0,
+            self.min_frame_time.as_secs_f64() * 1000.0,
+            self.max_frame_time.as_secs_f64() * 1000.0,
+        )
+    }
+}
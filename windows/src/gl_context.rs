@@ -1,35 +1,198 @@
+use crate::config::SurfacePreferences;
+use crate::gl_debug::{self, DebugOutput};
 use crate::winit_compat::NonZeroU32PhysicalSize;
 
-use std::ffi::CString;
+use std::ffi::{c_void, CStr, CString};
 use std::fmt;
+use std::num::NonZeroU32;
 use std::rc::Rc;
 
 use raw_window_handle::{RawDisplayHandle, RawWindowHandle};
+use serde::Serialize;
 use winit::dpi::PhysicalSize;
 
 use glow as GL;
 use glow::HasContext;
-use glutin::config::{ColorBufferType, Config as GLConfig, ConfigTemplateBuilder};
-use glutin::context::{ContextApi, ContextAttributesBuilder, PossiblyCurrentContext, Version};
+use glutin::config::{Config, ConfigSurfaceTypes, ConfigTemplateBuilder};
+use glutin::context::{
+    ContextApi, ContextAttributesBuilder, GlProfile, PossiblyCurrentContext, Robustness, Version,
+};
 use glutin::display::{Display, DisplayApiPreference, GetGlDisplay};
 use glutin::prelude::*;
-use glutin::surface::{Surface, SurfaceAttributesBuilder, WindowSurface};
+use glutin::surface::{PbufferSurface, Surface, SurfaceAttributesBuilder, WindowSurface};
 
 pub struct GLContext {
     pub context: PossiblyCurrentContext,
     pub surface: Surface<WindowSurface>,
     pub gl: Rc<glow::Context>,
+    // Every config the display offered, for reporting
+    pub configs: Vec<HumanConfig>,
+    pub config_index: usize,
+    // Dropped first, while the context is current, to remove the callback
+    pub debug_output: Option<DebugOutput>,
+    // Only set for robust contexts, which report driver resets
+    reset_status: Option<GetGraphicsResetStatus>,
 }
 
+impl Drop for GLContext {
+    fn drop(&mut self) {
+        if let Some(debug_output) = self.debug_output.take() {
+            drop_debug_output(debug_output, self.context.make_current(&self.surface));
+        }
+    }
+}
+
+// The driver would keep calling into the debug output after we free it, so only let it go if we
+// could make its context current to remove the callback. Otherwise, leak it.
+fn drop_debug_output(debug_output: DebugOutput, made_current: glutin::error::Result<()>) {
+    match made_current {
+        Ok(()) => drop(debug_output),
+        Err(err) => {
+            log::debug!("Can’t remove the debug message callback: {}", err);
+            std::mem::forget(debug_output);
+        }
+    }
+}
+
+impl GLContext {
+    /// The config we picked.
+    pub fn config(&self) -> &HumanConfig {
+        &self.configs[self.config_index]
+    }
+
+    /// Whether the driver reset the GPU and took the context with it, like after a TDR on Windows.
+    /// A lost context silently ignores every call, so it has to be recreated.
+    ///
+    /// Only robust contexts can tell. The context has to be current.
+    pub fn is_lost(&self) -> bool {
+        match self.reset_status {
+            Some(get_graphics_reset_status) => unsafe {
+                get_graphics_reset_status() != GL::NO_ERROR
+            },
+            None => false,
+        }
+    }
+
+    /// Whether `is_lost` can tell, because the context is robust.
+    pub fn can_report_reset(&self) -> bool {
+        self.reset_status.is_some()
+    }
+
+    /// Whether a failure to swap or present means we lost the context. The context has to be
+    /// current.
+    ///
+    /// Without robustness, there’s no way to ask, so assume the worst. A needless rebuild beats a
+    /// black screen, and the rebuild limit stops us if it isn’t a loss.
+    pub fn is_lost_after_failure(&self) -> bool {
+        !self.can_report_reset() || self.is_lost()
+    }
+}
+
+type GetGraphicsResetStatus = unsafe extern "system" fn() -> u32;
+
+// Look up glGetGraphicsResetStatus from whichever robustness extension the driver supports.
+fn load_reset_status(
+    gl: &glow::Context,
+    get_proc_address: impl Fn(&CStr) -> *const c_void,
+) -> Option<GetGraphicsResetStatus> {
+    let version = gl.version();
+    let extensions = gl.supported_extensions();
+
+    let name: &[u8] = if !version.is_embedded && (version.major, version.minor) >= (4, 5) {
+        b"glGetGraphicsResetStatus\0"
+    } else if extensions.contains("GL_KHR_robustness") {
+        if version.is_embedded {
+            b"glGetGraphicsResetStatusKHR\0"
+        } else {
+            b"glGetGraphicsResetStatus\0"
+        }
+    } else if extensions.contains("GL_ARB_robustness") {
+        b"glGetGraphicsResetStatusARB\0"
+    } else if extensions.contains("GL_EXT_robustness") {
+        b"glGetGraphicsResetStatusEXT\0"
+    } else {
+        log::debug!("The driver can’t report context resets");
+        return None;
+    };
+
+    let address = get_proc_address(CStr::from_bytes_with_nul(name).ok()?);
+
+    (!address.is_null())
+        .then(|| unsafe { std::mem::transmute::<*const c_void, GetGraphicsResetStatus>(address) })
+}
+
+/// Why we couldn’t set up OpenGL for a window.
+#[derive(Debug)]
+pub enum GLContextError {
+    /// Surfaces can’t have a zero width or height.
+    ZeroSize,
+    /// Neither WGL nor EGL could open the display.
+    Display(glutin::error::Error),
+    /// The display refused to list its configs.
+    FindConfigs(glutin::error::Error),
+    /// The display doesn’t offer any configs that work with the window.
+    NoConfig,
+    /// None of the APIs in the fallback chain could create a context.
+    Context(glutin::error::Error),
+    Surface(glutin::error::Error),
+    MakeCurrent(glutin::error::Error),
+}
+
+impl fmt::Display for GLContextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GLContextError::ZeroSize => {
+                write!(f, "Can’t create an OpenGL surface with a zero size")
+            }
+            GLContextError::Display(err) => write!(f, "Failed to open the OpenGL display: {}", err),
+            GLContextError::FindConfigs(err) => {
+                write!(f, "Failed to list the OpenGL configs: {}", err)
+            }
+            GLContextError::NoConfig => write!(f, "Can’t find a suitable OpenGL config"),
+            GLContextError::Context(err) => {
+                write!(f, "Failed to create an OpenGL context: {}", err)
+            }
+            GLContextError::Surface(err) => {
+                write!(f, "Failed to create an OpenGL surface: {}", err)
+            }
+            GLContextError::MakeCurrent(err) => {
+                write!(f, "Failed to make the OpenGL context current: {}", err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GLContextError {}
+
+// The APIs to try for each config, in order. Flux requires either OpenGL 3.3 or GLES 3.0 (aka
+// WebGL 2.0).
+const APIS: [(&str, ContextApi, Option<GlProfile>); 2] = [
+    (
+        "OpenGL 3.3 core",
+        ContextApi::OpenGl(Some(Version::new(3, 3))),
+        Some(GlProfile::Core),
+    ),
+    ("GLES 3.0", ContextApi::Gles(Some(Version::new(3, 0))), None),
+];
+
 /// Create an OpenGL context, surface, and initialize the glow API.
 ///
+/// Fallbacks
+///
+/// We try, in order: OpenGL 3.3 core, then GLES 3.0, each with the best transparent config with
+/// an alpha channel. With `relaxed`, the config drops transparency and alpha instead. The error is
+/// from the last attempt.
+///
+/// WGL sets the pixel format of the window along with the first context, and a window’s pixel
+/// format can’t change, so retry with a relaxed config on a new window.
+///
 /// Hacks
 ///
 /// The optional attr_window should be used when rendering to the preview window. Instead of just
 /// using the handle to the preview window, pass the window handle for the invisible event window
 /// to work around a bug where Windows complains that it can't find the window class.
 ///
-/// This code has been modified from glutin-winit and only supports WGL (Windows).
+/// This code has been modified from glutin-winit and supports WGL (Windows), GLX (X11), and EGL.
 pub(crate) fn new_gl_context(
     raw_display_handle: RawDisplayHandle,
     inner_size: PhysicalSize<u32>,
@@ -38,49 +201,235 @@ pub(crate) fn new_gl_context(
     // A hack to create the gl_display using the invisible event window
     // we create for the preview.
     attr_window: Option<RawWindowHandle>,
-) -> GLContext {
-    let template = ConfigTemplateBuilder::new()
+    preferences: &SurfacePreferences,
+    relaxed: bool,
+) -> Result<GLContext, GLContextError> {
+    let (width, height) = inner_size.non_zero().ok_or(GLContextError::ZeroSize)?;
+
+    let preference =
+        display_api_preference(raw_display_handle, attr_window.unwrap_or(raw_window_handle));
+    log::debug!("Opening the display with {:?}", preference);
+    let gl_display =
+        unsafe { Display::new(raw_display_handle, preference) }.map_err(GLContextError::Display)?;
+    log::debug!("Opened the display: {}", gl_display.version_string());
+
+    // Ask the driver to explain its errors, at the cost of some speed, when we’d log them.
+    let debug = wants_debug_output();
+    let mut last_error = GLContextError::NoConfig;
+
+    let (configs, gl_config_index, gl_config) =
+        pick_config(&gl_display, raw_window_handle, relaxed, preferences)?;
+
+    let use_msaa = preferences.msaa_samples > 0 && gl_config.num_samples() > 0;
+    let use_srgb = preferences.srgb && gl_config.srgb_capable();
+
+    // Prefer a robust context, so that we find out when the driver resets.
+    let attempts = APIS.into_iter().flat_map(|api| [(api, true), (api, false)]);
+
+    for ((name, api, profile), robust) in attempts {
+        let robustness = if robust {
+            Robustness::RobustLoseContextOnReset
+        } else {
+            Robustness::NotRobust
+        };
+        let mut context_attributes = ContextAttributesBuilder::new()
+            .with_context_api(api)
+            .with_robustness(robustness)
+            .with_debug(debug);
+        if let Some(profile) = profile {
+            context_attributes = context_attributes.with_profile(profile);
+        }
+        let context_attributes = context_attributes.build(Some(raw_window_handle));
+
+        let attempt = unsafe { gl_display.create_context(&gl_config, &context_attributes) }
+            .map_err(GLContextError::Context)
+            .and_then(|not_current_gl_context| {
+                // Only EGL reads this. Elsewhere, the framebuffer state decides.
+                let attrs = SurfaceAttributesBuilder::<WindowSurface>::new()
+                    .with_srgb(Some(use_srgb))
+                    .build(raw_window_handle, width, height);
+                let gl_surface = unsafe {
+                    gl_config
+                        .display()
+                        .create_window_surface(&gl_config, &attrs)
+                }
+                .map_err(GLContextError::Surface)?;
+
+                let gl_context = not_current_gl_context
+                    .make_current(&gl_surface)
+                    .map_err(GLContextError::MakeCurrent)?;
+
+                Ok((gl_context, gl_surface))
+            });
+
+        let (gl_context, gl_surface) = match attempt {
+            Ok(created) => created,
+            Err(err) if robust => {
+                log::debug!("Can’t create a robust {name} context: {err}");
+                last_error = err;
+                continue;
+            }
+            Err(err) => {
+                log::warn!(
+                    "Can’t use {name} with {} config: {err}",
+                    if relaxed {
+                        "a relaxed"
+                    } else {
+                        "the preferred"
+                    }
+                );
+                last_error = err;
+                continue;
+            }
+        };
+
+        log::debug!("Created an {name} context (robust: {robust})");
+
+        let glow_context = unsafe {
+            glow::Context::from_loader_function(|s| {
+                gl_display.get_proc_address(CString::new(s).unwrap().as_c_str()) as *const _
+            })
+        };
+        log::debug!("{:?}", glow_context.version());
+
+        let debug_output = debug
+            .then(|| gl_debug::install(&glow_context, |name| gl_display.get_proc_address(name)))
+            .flatten();
+        let reset_status = robust
+            .then(|| load_reset_status(&glow_context, |name| gl_display.get_proc_address(name)))
+            .flatten();
+
+        // Set common GL state
+        unsafe {
+            // GLES always multisamples a surface with samples, and treats MULTISAMPLE as an
+            // invalid enum.
+            if !glow_context.version().is_embedded {
+                if use_msaa {
+                    glow_context.enable(GL::MULTISAMPLE);
+                } else {
+                    glow_context.disable(GL::MULTISAMPLE);
+                }
+            }
+
+            // GLES converts to sRGB based on the surface alone.
+            if use_srgb && !glow_context.version().is_embedded {
+                glow_context.enable(GL::FRAMEBUFFER_SRGB);
+            }
+            // glow_context.disable(GL::STENCIL_TEST);
+            // glow_context.disable(GL::DEPTH_TEST);
+        }
+
+        return Ok(GLContext {
+            context: gl_context,
+            surface: gl_surface,
+            gl: Rc::new(glow_context),
+            configs,
+            config_index: gl_config_index,
+            debug_output,
+            reset_status,
+        });
+    }
+
+    Err(last_error)
+}
+
+// Pick the platform API for the display. On X11, GLX is the most widely supported, but EGL also
+// works with Mesa, including under Xvfb. Wayland only has EGL.
+fn display_api_preference(
+    raw_display_handle: RawDisplayHandle,
+    raw_window_handle: RawWindowHandle,
+) -> DisplayApiPreference {
+    // Only WGL requires a window to create a full-fledged OpenGL context
+    #[cfg(windows)]
+    {
+        let _ = raw_display_handle;
+        DisplayApiPreference::WglThenEgl(Some(raw_window_handle))
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        let _ = raw_window_handle;
+        match raw_display_handle {
+            RawDisplayHandle::Xlib(_) | RawDisplayHandle::Xcb(_) => {
+                use crate::platform::unix::xlib_error_hook;
+                DisplayApiPreference::GlxThenEgl(xlib_error_hook::registrar())
+            }
+            _ => DisplayApiPreference::Egl,
+        }
+    }
+
+    #[cfg(not(any(windows, all(unix, not(target_os = "macos")))))]
+    {
+        let _ = (raw_display_handle, raw_window_handle);
+        DisplayApiPreference::Egl
+    }
+}
+
+// Pick the config that best matches the preferences for the window. The relaxed config doesn’t ask
+// for transparency or an alpha channel, which some drivers can’t provide.
+fn pick_config(
+    gl_display: &Display,
+    raw_window_handle: RawWindowHandle,
+    relaxed: bool,
+    preferences: &SurfacePreferences,
+) -> Result<(Vec<HumanConfig>, usize, Config), GLContextError> {
+    let mut template = ConfigTemplateBuilder::new()
         .with_buffer_type(glutin::config::ColorBufferType::Rgb {
             r_size: 8,
             g_size: 8,
             b_size: 8,
         })
-        .with_alpha_size(8)
-        .with_transparency(true)
-        .compatible_with_native_window(raw_window_handle)
-        .build();
+        .compatible_with_native_window(raw_window_handle);
 
-    // Only WGL requires a window to create a full-fledged OpenGL context
-    let attr_window = attr_window.unwrap_or(raw_window_handle);
-    let preference = DisplayApiPreference::WglThenEgl(Some(attr_window));
-    let gl_display = unsafe { Display::new(raw_display_handle, preference).unwrap() };
+    if !relaxed {
+        template = template.with_alpha_size(8).with_transparency(true);
+    }
 
-    // Rank the configs by transparency and alpha size, while prefering the original order of the
-    // configs.
+    // Rank the configs by transparency and alpha size, then by how close they come to the
+    // requested multisampling and sRGB, while prefering the original order of the configs.
     #[derive(Debug, Eq, Ord, PartialEq, PartialOrd)]
     struct Rank {
         supports_transparency: bool,
         alpha_size: u8,
-        samples: i8,
+        samples_distance: std::cmp::Reverse<u8>,
+        matches_srgb: bool,
         prefer_original_order: isize,
     }
 
+    let mut configs = Vec::new();
+
     let (gl_config_index, gl_config) = unsafe {
         gl_display
-            .find_configs(template)
-            .unwrap()
+            .find_configs(template.build())
+            .map_err(GLContextError::FindConfigs)?
             .enumerate()
-            .map(|(index, config)| {
-                log::debug!("Found config #{index}:\n{}", HumanConfig::new(&config));
-                (index, config)
-            })
-            .max_by_key(|(index, config)| Rank {
-                supports_transparency: config.supports_transparency().unwrap_or(false),
-                alpha_size: config.alpha_size(),
-                samples: -(config.num_samples() as i8),
-                prefer_original_order: -(*index as isize),
+            .filter_map(|(index, config)| {
+                let human_config = HumanConfig::new(&config);
+                log::debug!("Found config #{index}:\n{}", human_config);
+                configs.push(human_config);
+
+                let supports_transparency = config.supports_transparency().unwrap_or(false);
+                if preferences.require_transparency && !supports_transparency {
+                    log::debug!("Rejected config #{index}: it doesn’t support transparency");
+                    return None;
+                }
+
+                let rank = Rank {
+                    supports_transparency,
+                    alpha_size: config.alpha_size(),
+                    samples_distance: std::cmp::Reverse(
+                        config.num_samples().abs_diff(preferences.msaa_samples),
+                    ),
+                    matches_srgb: config.srgb_capable() == preferences.srgb,
+                    prefer_original_order: -(index as isize),
+                };
+                log::debug!("Accepted config #{index}: {:?}", rank);
+
+                Some((rank, index, config))
             })
-            .expect("cannot find a suitable GL config")
+            .max_by(|(a, _, _), (b, _, _)| a.cmp(b))
+            .map(|(_, index, config)| (index, config))
+            .ok_or(GLContextError::NoConfig)?
     };
 
     log::debug!(
@@ -88,39 +437,97 @@ pub(crate) fn new_gl_context(
         HumanConfig::new(&gl_config)
     );
 
-    // Request the minimum required OpenGL version for Flux
+    if preferences.msaa_samples > 0 && gl_config.num_samples() != preferences.msaa_samples {
+        log::warn!(
+            "Asked for {} MSAA samples, but the closest config has {}",
+            preferences.msaa_samples,
+            gl_config.num_samples()
+        );
+    }
+
+    if preferences.srgb && !gl_config.srgb_capable() {
+        log::warn!("Asked for an sRGB framebuffer, but none of the configs support it");
+    }
+
+    Ok((configs, gl_config_index, gl_config))
+}
+
+pub struct HeadlessGLContext {
+    pub context: glutin::api::egl::context::PossiblyCurrentContext,
+    pub surface: glutin::api::egl::surface::Surface<PbufferSurface>,
+    pub gl: Rc<glow::Context>,
+    // Dropped first, while the context is current, to remove the callback
+    pub debug_output: Option<DebugOutput>,
+}
+
+impl Drop for HeadlessGLContext {
+    fn drop(&mut self) {
+        if let Some(debug_output) = self.debug_output.take() {
+            drop_debug_output(debug_output, self.context.make_current(&self.surface));
+        }
+    }
+}
+
+/// Create an OpenGL context that doesn’t need a window or a display server.
+///
+/// This goes straight to an EGL device, which works on machines without a GPU as long as there’s
+/// a software renderer, like Mesa’s llvmpipe. Render into an offscreen framebuffer: the context
+/// only has a tiny pbuffer surface to make it current.
+pub(crate) fn new_headless_gl_context() -> Result<HeadlessGLContext, String> {
+    use glutin::api::egl::{device::Device, display::Display as EglDisplay};
+
+    let gl_display = Device::query_devices()
+        .map_err(|err| format!("Failed to list EGL devices: {}", err))?
+        .find_map(|device| {
+            log::debug!(
+                "Found EGL device: {} ({})",
+                device.name().unwrap_or("unknown"),
+                device.vendor().unwrap_or("unknown vendor")
+            );
+
+            unsafe { EglDisplay::with_device(&device, None) }
+                .map_err(|err| log::debug!("Can’t use the EGL device: {}", err))
+                .ok()
+        })
+        .ok_or("None of the EGL devices support headless rendering")?;
+
+    let template = ConfigTemplateBuilder::new()
+        .with_alpha_size(8)
+        .with_surface_type(ConfigSurfaceTypes::PBUFFER)
+        .build();
+
+    let gl_config = unsafe { gl_display.find_configs(template) }
+        .map_err(|err| format!("Failed to list EGL configs: {}", err))?
+        .next()
+        .ok_or("None of the EGL configs support offscreen rendering")?;
+
+    log::debug!("Picked config:\n{}", HumanConfig::new(&gl_config));
+
+    let debug = wants_debug_output();
     let context_attributes = ContextAttributesBuilder::new()
         .with_context_api(ContextApi::OpenGl(Some(Version::new(3, 3))))
-        .build(Some(raw_window_handle));
-
-    // Fallback to GLES 3.0 (aka WebGL 2.0)
+        .with_debug(debug)
+        .build(None);
     let fallback_context_attributes = ContextAttributesBuilder::new()
         .with_context_api(ContextApi::Gles(Some(Version::new(3, 0))))
-        .build(Some(raw_window_handle));
+        .with_debug(debug)
+        .build(None);
 
     let not_current_gl_context = unsafe {
         gl_display
             .create_context(&gl_config, &context_attributes)
-            .unwrap_or_else(|_| {
-                gl_display
-                    .create_context(&gl_config, &fallback_context_attributes)
-                    .expect("failed to create OpenGL context")
-            })
-    };
-
-    let (width, height) = inner_size.non_zero().expect("non-zero window size");
-    let attrs =
-        SurfaceAttributesBuilder::<WindowSurface>::new().build(raw_window_handle, width, height);
+            .or_else(|_| gl_display.create_context(&gl_config, &fallback_context_attributes))
+    }
+    .map_err(|err| format!("Failed to create an OpenGL context: {}", err))?;
 
-    let gl_surface = unsafe {
-        gl_config
-            .display()
-            .create_window_surface(&gl_config, &attrs)
-            .unwrap()
-    };
+    let one = NonZeroU32::new(1).unwrap();
+    let attrs = SurfaceAttributesBuilder::<PbufferSurface>::new().build(one, one);
+    let gl_surface = unsafe { gl_display.create_pbuffer_surface(&gl_config, &attrs) }
+        .map_err(|err| format!("Failed to create a pbuffer surface: {}", err))?;
 
-    // Make it current.
-    let gl_context = not_current_gl_context.make_current(&gl_surface).unwrap();
+    let gl_context = not_current_gl_context
+        .make_current(&gl_surface)
+        .map_err(|err| format!("Failed to make the OpenGL context current: {}", err))?;
 
     let glow_context = unsafe {
         glow::Context::from_loader_function(|s| {
@@ -129,23 +536,27 @@ pub(crate) fn new_gl_context(
     };
     log::debug!("{:?}", glow_context.version());
 
-    // Set common GL state
-    unsafe {
-        glow_context.disable(GL::MULTISAMPLE);
-        // glow_context.disable(GL::STENCIL_TEST);
-        // glow_context.disable(GL::DEPTH_TEST);
-    }
+    let debug_output = debug
+        .then(|| gl_debug::install(&glow_context, |name| gl_display.get_proc_address(name)))
+        .flatten();
 
-    GLContext {
+    Ok(HeadlessGLContext {
         context: gl_context,
         surface: gl_surface,
         gl: Rc::new(glow_context),
-    }
+        debug_output,
+    })
 }
 
-#[derive(Debug)]
-struct HumanConfig {
-    color_buffer_type: Option<ColorBufferType>,
+// Debug contexts are slower, so only create one if the driver’s messages would make it into the
+// log.
+fn wants_debug_output() -> bool {
+    log::max_level() >= log::LevelFilter::Debug
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct HumanConfig {
+    color_buffer_type: String,
     alpha_size: u8,
     depth_size: u8,
     stencil_size: u8,
@@ -157,9 +568,9 @@ struct HumanConfig {
 }
 
 impl HumanConfig {
-    fn new(config: &GLConfig) -> Self {
+    fn new(config: &impl GlConfig) -> Self {
         Self {
-            color_buffer_type: config.color_buffer_type(),
+            color_buffer_type: format!("{:?}", config.color_buffer_type()),
             alpha_size: config.alpha_size(),
             depth_size: config.depth_size(),
             stencil_size: config.stencil_size(),
@@ -187,7 +598,7 @@ impl fmt::Display for HumanConfig {
         } = self;
         write!(
             f,
-            "Color buffer type: {color_buffer_type:?}\n\
+            "Color buffer type: {color_buffer_type}\n\
                Alpha size: {alpha_size}\n\
                Depth size: {depth_size}\n\
                Stencil size: {stencil_size}\n\
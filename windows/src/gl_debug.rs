@@ -0,0 +1,195 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::ffi::{c_void, CStr};
+use std::hash::{Hash, Hasher};
+use std::os::raw::c_char;
+use std::sync::Mutex;
+
+use glow as GL;
+use glow::HasContext;
+
+// After the first message, only log every this many repeats of the same message. Some drivers
+// report the same problem every frame.
+const REPEAT_INTERVAL: u32 = 1000;
+
+// Forget the counts once we’ve seen this many different messages. Some messages include details
+// that change every time, like addresses, and would otherwise fill up the map.
+const MAX_SEEN: usize = 1024;
+
+type DebugProc = extern "system" fn(
+    source: u32,
+    message_type: u32,
+    id: u32,
+    severity: u32,
+    length: i32,
+    message: *const c_char,
+    user_param: *mut c_void,
+);
+
+type DebugMessageCallback =
+    unsafe extern "system" fn(callback: Option<DebugProc>, user_param: *mut c_void);
+
+/// Routes the driver’s debug messages into the log, for as long as it lives. Drop it while its
+/// context is current, so that it can remove the callback.
+pub struct DebugOutput {
+    debug_message_callback: DebugMessageCallback,
+    // The driver holds a pointer to this, so it has to stay put.
+    _state: Box<DebugState>,
+}
+
+impl Drop for DebugOutput {
+    fn drop(&mut self) {
+        unsafe { (self.debug_message_callback)(None, std::ptr::null_mut()) };
+    }
+}
+
+struct DebugState {
+    // How many times we’ve seen each message, by source, type, id, and a hash of the text. Drivers
+    // reuse ids, like 0, for different messages.
+    seen: Mutex<HashMap<(u32, u32, u32, u64), u32>>,
+}
+
+/// Install a debug message callback with `KHR_debug`, or `ARB_debug_output` on older drivers.
+///
+/// Only call this on a debug context. Returns `None` if the driver doesn’t support either.
+pub fn install(
+    gl: &glow::Context,
+    get_proc_address: impl Fn(&CStr) -> *const c_void,
+) -> Option<DebugOutput> {
+    let names: &[&[u8]] = if gl.supports_debug() {
+        &[b"glDebugMessageCallback\0", b"glDebugMessageCallbackKHR\0"]
+    } else if gl.supported_extensions().contains("GL_ARB_debug_output") {
+        &[b"glDebugMessageCallbackARB\0"]
+    } else {
+        log::debug!("The driver doesn’t support debug output");
+        return None;
+    };
+
+    let debug_message_callback = names.iter().find_map(|name| {
+        let name = CStr::from_bytes_with_nul(name).ok()?;
+        let address = get_proc_address(name);
+
+        (!address.is_null())
+            .then(|| unsafe { std::mem::transmute::<*const c_void, DebugMessageCallback>(address) })
+    })?;
+
+    let state = Box::new(DebugState {
+        seen: Mutex::new(HashMap::new()),
+    });
+
+    unsafe {
+        // GL_DEBUG_OUTPUT only exists with KHR_debug. With ARB_debug_output, it’s always on for a
+        // debug context.
+        if gl.supports_debug() {
+            gl.enable(GL::DEBUG_OUTPUT);
+        }
+
+        // Report messages on the thread that caused them, while the offending call is still on
+        // the stack.
+        gl.enable(GL::DEBUG_OUTPUT_SYNCHRONOUS);
+
+        debug_message_callback(
+            Some(log_message),
+            &*state as *const DebugState as *mut c_void,
+        );
+    }
+
+    log::debug!("Logging OpenGL debug messages");
+
+    Some(DebugOutput {
+        debug_message_callback,
+        _state: state,
+    })
+}
+
+extern "system" fn log_message(
+    source: u32,
+    message_type: u32,
+    id: u32,
+    severity: u32,
+    length: i32,
+    message: *const c_char,
+    user_param: *mut c_void,
+) {
+    if message.is_null() || user_param.is_null() {
+        return;
+    }
+
+    let state = unsafe { &*(user_param as *const DebugState) };
+
+    let message = unsafe {
+        if length >= 0 {
+            let bytes = std::slice::from_raw_parts(message as *const u8, length as usize);
+            String::from_utf8_lossy(bytes).into_owned()
+        } else {
+            CStr::from_ptr(message).to_string_lossy().into_owned()
+        }
+    };
+    let message = message.trim_end();
+
+    let mut hasher = DefaultHasher::new();
+    message.hash(&mut hasher);
+    let key = (source, message_type, id, hasher.finish());
+
+    let repeats = match state.seen.lock() {
+        Ok(mut seen) => {
+            if seen.len() >= MAX_SEEN && !seen.contains_key(&key) {
+                seen.clear();
+            }
+
+            let count = seen.entry(key).or_insert(0);
+            *count += 1;
+            *count
+        }
+        Err(_) => 1,
+    };
+
+    if repeats != 1 && repeats % REPEAT_INTERVAL != 0 {
+        return;
+    }
+
+    let level = match (message_type, severity) {
+        (GL::DEBUG_TYPE_ERROR, _) | (_, GL::DEBUG_SEVERITY_HIGH) => log::Level::Error,
+        (_, GL::DEBUG_SEVERITY_MEDIUM) => log::Level::Warn,
+        (_, GL::DEBUG_SEVERITY_LOW) => log::Level::Info,
+        _ => log::Level::Debug,
+    };
+
+    if repeats == 1 {
+        log::log!(
+            level,
+            "OpenGL {} ({}, id {id}): {message}",
+            type_name(message_type),
+            source_name(source)
+        );
+    } else {
+        log::log!(
+            level,
+            "OpenGL {} ({}, id {id}), repeated {repeats} times: {message}",
+            type_name(message_type),
+            source_name(source)
+        );
+    }
+}
+
+fn source_name(source: u32) -> &'static str {
+    match source {
+        GL::DEBUG_SOURCE_API => "API",
+        GL::DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
+        GL::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+        GL::DEBUG_SOURCE_THIRD_PARTY => "third party",
+        GL::DEBUG_SOURCE_APPLICATION => "application",
+        _ => "other source",
+    }
+}
+
+fn type_name(message_type: u32) -> &'static str {
+    match message_type {
+        GL::DEBUG_TYPE_ERROR => "error",
+        GL::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated behavior",
+        GL::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behavior",
+        GL::DEBUG_TYPE_PORTABILITY => "portability issue",
+        GL::DEBUG_TYPE_PERFORMANCE => "performance issue",
+        _ => "message",
+    }
+}
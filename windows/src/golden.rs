@@ -0,0 +1,221 @@
+use crate::config::{ColorMode, Config};
+use crate::gl_context;
+use crate::headless;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use flux::settings::ColorPreset;
+use image::RgbaImage;
+use winit::dpi::PhysicalSize;
+
+// Keep the references small enough to check into the repo.
+const SIZE: PhysicalSize<u32> = PhysicalSize::new(320, 240);
+const FPS: u32 = 60;
+const FRAMES: u32 = 120;
+const SEED: &str = "golden";
+
+// How different two pixels can look before we count them as changed, from 0 to 1. This is the
+// same perceptual threshold pixelmatch uses.
+const PIXEL_THRESHOLD: f32 = 0.1;
+
+// The largest share of changed pixels we accept. Drivers and software rasterizers don’t agree on
+// every last bit, so a handful of changed pixels is expected.
+const MAX_CHANGED_RATIO: f32 = 0.005;
+
+// The references live next to the sources. Failures write the actual frames and the diffs to
+// `golden-results`, so that CI can upload them.
+const REFERENCES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/golden");
+const RESULTS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/golden-results");
+
+// Set this to replace the references with the current rendering instead of comparing against
+// them.
+const UPDATE_VAR: &str = "FLUX_UPDATE_GOLDEN";
+
+// Render every color mode for a fixed number of steps and compare the last frame against the
+// reference images. It needs an EGL device, and the references only match the rasterizer they
+// came from, so it’s ignored by default. See “Rendering tests” in the README.
+#[test]
+#[ignore]
+fn it_renders_like_the_references() {
+    if let Err(err) = run() {
+        panic!("{}", err);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let references = Path::new(REFERENCES);
+    let results = Path::new(RESULTS);
+    let update = std::env::var_os(UPDATE_VAR).is_some();
+
+    let gl_context = gl_context::new_headless_gl_context()?;
+    let wallpaper = write_test_wallpaper(results)?;
+    let mut failures = Vec::new();
+
+    for (name, color_mode) in color_modes() {
+        let mut config = Config::default();
+        config.flux.color_mode = color_mode;
+        config.flux.seed = Some(SEED.to_string());
+
+        let mut surface = headless::offscreen_surface(SIZE);
+        surface.wallpaper = Some(wallpaper.clone());
+        let settings = Rc::new(config.to_settings(&surface));
+
+        let mut last_frame = Vec::new();
+        headless::render_frames(&gl_context.gl, SIZE, &settings, FPS, FRAMES, |_, pixels| {
+            last_frame = pixels;
+            Ok(())
+        })?;
+        let actual = RgbaImage::from_raw(SIZE.width, SIZE.height, last_frame)
+            .ok_or("The rendered frame doesn’t match the size of the references")?;
+
+        let reference_path = references.join(format!("{name}.png"));
+
+        if update {
+            fs::create_dir_all(references).map_err(|err| err.to_string())?;
+            save(&actual, &reference_path)?;
+            println!("{name}: updated {}", reference_path.display());
+            continue;
+        }
+
+        // There’s nothing to compare against until someone renders the references on CI.
+        if !reference_path.exists() {
+            println!(
+                "{name}: skipped, there’s no reference at {}. Run with {}=1 to create it.",
+                reference_path.display(),
+                UPDATE_VAR
+            );
+            continue;
+        }
+
+        let reference = image::open(&reference_path)
+            .map_err(|err| {
+                format!(
+                    "Failed to read the reference {}: {}",
+                    reference_path.display(),
+                    err
+                )
+            })?
+            .to_rgba8();
+
+        match compare(&reference, &actual) {
+            Comparison::Match => println!("{name}: ok"),
+            Comparison::SizeMismatch => {
+                println!("{name}: the reference has a different size");
+                failures.push(name);
+            }
+            Comparison::Changed { ratio, diff } => {
+                let actual_path = results.join(format!("{name}-actual.png"));
+                let diff_path = results.join(format!("{name}-diff.png"));
+                save(&actual, &actual_path)?;
+                save(&diff, &diff_path)?;
+
+                println!(
+                    "{name}: {:.2}% of pixels changed. See {}",
+                    ratio * 100.0,
+                    diff_path.display()
+                );
+                failures.push(name);
+            }
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Rendering changed for: {}. If that’s expected, run with {}=1.",
+            failures.join(", "),
+            UPDATE_VAR
+        ))
+    }
+}
+
+fn color_modes() -> [(&'static str, ColorMode); 4] {
+    [
+        ("original", ColorMode::Preset(ColorPreset::Original)),
+        ("plasma", ColorMode::Preset(ColorPreset::Plasma)),
+        ("poolside", ColorMode::Preset(ColorPreset::Poolside)),
+        ("desktop-image", ColorMode::DesktopImage),
+    ]
+}
+
+enum Comparison {
+    Match,
+    SizeMismatch,
+    Changed { ratio: f32, diff: RgbaImage },
+}
+
+fn compare(reference: &RgbaImage, actual: &RgbaImage) -> Comparison {
+    if reference.dimensions() != actual.dimensions() {
+        return Comparison::SizeMismatch;
+    }
+
+    let max_delta = MAX_YIQ_DELTA * PIXEL_THRESHOLD * PIXEL_THRESHOLD;
+    let mut changed = 0;
+
+    // Show the reference faded out, with the changed pixels in red.
+    let diff = RgbaImage::from_fn(reference.width(), reference.height(), |x, y| {
+        let expected = reference.get_pixel(x, y).0;
+        let pixel = actual.get_pixel(x, y).0;
+
+        if yiq_delta(expected, pixel) > max_delta {
+            changed += 1;
+            image::Rgba([255, 0, 0, 255])
+        } else {
+            let luma = (255.0 - 0.1 * (255.0 - luma(expected))) as u8;
+            image::Rgba([luma, luma, luma, 255])
+        }
+    });
+
+    let ratio = changed as f32 / (reference.width() * reference.height()) as f32;
+
+    if ratio > MAX_CHANGED_RATIO {
+        Comparison::Changed { ratio, diff }
+    } else {
+        Comparison::Match
+    }
+}
+
+// The largest possible value of `yiq_delta`.
+const MAX_YIQ_DELTA: f32 = 35215.0;
+
+// The perceived difference between two colors, from “Measuring perceived color difference using
+// YIQ NTSC transmission color space in mobile applications” by Kotsarenko and Ramos.
+fn yiq_delta(a: [u8; 4], b: [u8; 4]) -> f32 {
+    let [r1, g1, b1] = [a[0] as f32, a[1] as f32, a[2] as f32];
+    let [r2, g2, b2] = [b[0] as f32, b[1] as f32, b[2] as f32];
+
+    let y = (0.29889531 * r1 + 0.58662247 * g1 + 0.11448223 * b1)
+        - (0.29889531 * r2 + 0.58662247 * g2 + 0.11448223 * b2);
+    let i = (0.59597799 * r1 - 0.2741761 * g1 - 0.32180189 * b1)
+        - (0.59597799 * r2 - 0.2741761 * g2 - 0.32180189 * b2);
+    let q = (0.21147017 * r1 - 0.52261711 * g1 + 0.31114694 * b1)
+        - (0.21147017 * r2 - 0.52261711 * g2 + 0.31114694 * b2);
+
+    0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q
+}
+
+fn luma(pixel: [u8; 4]) -> f32 {
+    0.29889531 * pixel[0] as f32 + 0.58662247 * pixel[1] as f32 + 0.11448223 * pixel[2] as f32
+}
+
+// The desktop image mode needs a wallpaper. Generate one, so that it’s the same on every machine.
+fn write_test_wallpaper(directory: &Path) -> Result<PathBuf, String> {
+    fs::create_dir_all(directory).map_err(|err| err.to_string())?;
+
+    let wallpaper = RgbaImage::from_fn(256, 256, |x, y| {
+        image::Rgba([x as u8, y as u8, (255 - x.max(y)) as u8, 255])
+    });
+    let path = directory.join("test-wallpaper.png");
+    save(&wallpaper, &path)?;
+
+    Ok(path)
+}
+
+fn save(image: &RgbaImage, path: &Path) -> Result<(), String> {
+    image
+        .save(path)
+        .map_err(|err| format!("Failed to write {}: {}", path.display(), err))
+}
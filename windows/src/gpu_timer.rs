@@ -0,0 +1,98 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use glow as GL;
+use glow::HasContext;
+
+/// Measures how long the GPU spends on each frame with timer queries.
+///
+/// The results arrive a few frames late, so we keep a queue of queries in flight instead of
+/// waiting on each one, which would stall the pipeline and skew the timings.
+pub struct GpuTimer {
+    pending: VecDeque<GL::NativeQuery>,
+    free: Vec<GL::NativeQuery>,
+    samples: Vec<Duration>,
+    // Whether this frame’s query started. Creating a query can fail, and then there’s nothing to
+    // end, even if older queries are still pending.
+    active: bool,
+}
+
+impl GpuTimer {
+    /// Returns `None` if the context doesn’t support timer queries.
+    pub fn new(gl: &glow::Context) -> Option<Self> {
+        // Timer queries are core in OpenGL 3.3, but need an extension on GLES.
+        let supported = !gl.version().is_embedded
+            || gl
+                .supported_extensions()
+                .contains("GL_EXT_disjoint_timer_query");
+
+        supported.then(|| Self {
+            pending: VecDeque::new(),
+            free: Vec::new(),
+            samples: Vec::new(),
+            active: false,
+        })
+    }
+
+    pub fn begin(&mut self, gl: &glow::Context) {
+        let query = match self.free.pop() {
+            Some(query) => query,
+            None => match unsafe { gl.create_query() } {
+                Ok(query) => query,
+                Err(err) => {
+                    log::warn!("Failed to create a timer query: {}", err);
+                    return;
+                }
+            },
+        };
+
+        unsafe { gl.begin_query(GL::TIME_ELAPSED, query) };
+        self.pending.push_back(query);
+        self.active = true;
+    }
+
+    pub fn end(&mut self, gl: &glow::Context) {
+        if self.active {
+            unsafe { gl.end_query(GL::TIME_ELAPSED) };
+            self.active = false;
+        }
+    }
+
+    /// Read back the queries that have finished. With `wait`, block until all of them have.
+    pub fn collect(&mut self, gl: &glow::Context, wait: bool) {
+        while let Some(&query) = self.pending.front() {
+            let available =
+                unsafe { gl.get_query_parameter_u32(query, GL::QUERY_RESULT_AVAILABLE) } != 0;
+
+            if !available && !wait {
+                break;
+            }
+
+            // Blocks until the result is ready. A u32 holds a little over four seconds in
+            // nanoseconds, which is plenty for a frame.
+            let nanoseconds = unsafe { gl.get_query_parameter_u32(query, GL::QUERY_RESULT) };
+            self.samples.push(Duration::from_nanos(nanoseconds as u64));
+
+            self.pending.pop_front();
+            self.free.push(query);
+        }
+    }
+
+    /// The GPU time of every frame collected so far, in order.
+    pub fn samples(&self) -> &[Duration] {
+        &self.samples
+    }
+
+    /// Like `samples`, but hands them over and starts collecting from scratch.
+    pub fn take_samples(&mut self) -> Vec<Duration> {
+        std::mem::take(&mut self.samples)
+    }
+
+    pub fn delete(self, gl: &glow::Context) {
+        unsafe {
+            for query in self.pending.into_iter().chain(self.free) {
+                gl.delete_query(query);
+            }
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use crate::cli::RenderOptions;
+use crate::config::Config;
+use crate::gl_context;
+use crate::render_target::RenderTarget;
+use crate::surface::Surface;
+
+use std::fs;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use flux::settings::Settings;
+use flux::Flux;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+/// Render frames without a window, and write them out as a PNG sequence or an animated GIF.
+///
+/// The simulation advances by a fixed step per frame, so the same seed and settings always
+/// produce the same frames, no matter how long each one takes to render.
+pub fn run(config: &Config, options: &RenderOptions) -> Result<(), String> {
+    let gl_context = gl_context::new_headless_gl_context()?;
+
+    let surface = offscreen_surface(options.size);
+    let mut settings = config.to_settings(&surface);
+    if let Some(preset) = options.preset {
+        settings.color_mode = flux::settings::ColorMode::Preset(preset);
+    }
+
+    let mut writer = FrameWriter::new(&options.output, options.fps)?;
+
+    render_frames(
+        &gl_context.gl,
+        options.size,
+        &Rc::new(settings),
+        options.fps,
+        options.frames,
+        |frame, pixels| writer.write(frame, options.size, pixels),
+    )?;
+
+    log::info!(
+        "Wrote {} frames to {}",
+        options.frames,
+        options.output.display()
+    );
+
+    Ok(())
+}
+
+/// Render the simulation offscreen with a fixed step per frame, and hand each frame to `on_frame`
+/// as tightly packed RGBA rows, from top to bottom.
+pub fn render_frames(
+    gl: &Rc<glow::Context>,
+    size: PhysicalSize<u32>,
+    settings: &Rc<Settings>,
+    fps: u32,
+    frames: u32,
+    mut on_frame: impl FnMut(u32, Vec<u8>) -> Result<(), String>,
+) -> Result<(), String> {
+    let mut flux = Flux::new(
+        gl,
+        size.width,
+        size.height,
+        size.width,
+        size.height,
+        settings,
+    )
+    .map_err(|err| err.to_string())?;
+    let render_target = RenderTarget::new(gl, size)?;
+
+    let result = (0..frames).try_for_each(|frame| {
+        let timestamp = frame as f64 * 1000.0 / fps as f64;
+
+        crate::render_frame(
+            &mut flux,
+            gl,
+            None,
+            Some(render_target.framebuffer()),
+            size,
+            timestamp,
+        );
+
+        on_frame(frame, render_target.read_pixels(gl))
+    });
+
+    render_target.delete(gl);
+
+    result
+}
+
+enum FrameWriter {
+    PngSequence(PathBuf),
+    Gif {
+        path: PathBuf,
+        encoder: Box<GifEncoder<BufWriter<fs::File>>>,
+        delay: Delay,
+    },
+}
+
+impl FrameWriter {
+    // Pick the format from the extension of the output path. Anything without a known extension
+    // is a directory for a PNG sequence.
+    fn new(output: &Path, fps: u32) -> Result<Self, String> {
+        let extension = output
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("gif") => {
+                let file = fs::File::create(output)
+                    .map_err(|err| format!("Failed to create {}: {}", output.display(), err))?;
+                let mut encoder = GifEncoder::new(BufWriter::new(file));
+                encoder
+                    .set_repeat(Repeat::Infinite)
+                    .map_err(|err| err.to_string())?;
+
+                let centiseconds = gif_frame_delay(fps);
+                let effective_fps = 100.0 / centiseconds as f64;
+                if effective_fps != fps as f64 {
+                    log::info!(
+                        "GIFs can’t play at {} fps. The recording plays back at {:.1} fps.",
+                        fps,
+                        effective_fps
+                    );
+                }
+
+                Ok(Self::Gif {
+                    path: output.to_owned(),
+                    encoder: Box::new(encoder),
+                    delay: Delay::from_numer_denom_ms(centiseconds * 10, 1),
+                })
+            }
+
+            // The image crate can only encode still WebP images.
+            Some("webp") => Err(
+                "Animated WebP isn’t supported. Record a PNG sequence and convert it with img2webp."
+                    .to_string(),
+            ),
+
+            _ => {
+                fs::create_dir_all(output).map_err(|err| {
+                    format!(
+                        "Failed to create the output directory {}: {}",
+                        output.display(),
+                        err
+                    )
+                })?;
+
+                Ok(Self::PngSequence(output.to_owned()))
+            }
+        }
+    }
+
+    fn write(
+        &mut self,
+        frame: u32,
+        size: PhysicalSize<u32>,
+        pixels: Vec<u8>,
+    ) -> Result<(), String> {
+        match self {
+            Self::PngSequence(directory) => {
+                let path = directory.join(format!("frame-{:05}.png", frame));
+                write_png(&path, size, &pixels)
+            }
+
+            Self::Gif {
+                path,
+                encoder,
+                delay,
+            } => {
+                let image = RgbaImage::from_raw(size.width, size.height, pixels)
+                    .ok_or("The frame doesn’t match the size of the recording")?;
+
+                encoder
+                    .encode_frame(Frame::from_parts(image, 0, 0, *delay))
+                    .map_err(|err| format!("Failed to write {}: {}", path.display(), err))
+            }
+        }
+    }
+}
+
+// GIFs store frame delays in hundredths of a second, and the encoder truncates anything finer,
+// which would play 60 fps back at 100 fps. Round to the closest delay instead. Browsers slow
+// down delays under two hundredths to a tenth of a second, so that’s as fast as it goes.
+fn gif_frame_delay(fps: u32) -> u32 {
+    (100.0 / fps as f64).round().max(2.0) as u32
+}
+
+// A stand-in for a monitor, so that we pick up the same settings adjustments as a real screen.
+pub fn offscreen_surface(size: PhysicalSize<u32>) -> Surface {
+    Surface {
+        position: PhysicalPosition::new(0, 0),
+        size,
+        scale_factor: 1.0,
+        rotation: Default::default(),
+        wallpaper: None,
+        blackout: false,
+    }
+}
+
+pub fn write_png(path: &Path, size: PhysicalSize<u32>, pixels: &[u8]) -> Result<(), String> {
+    image::save_buffer(
+        path,
+        pixels,
+        size.width,
+        size.height,
+        image::ColorType::Rgba8,
+    )
+    .map_err(|err| format!("Failed to write {}: {}", path.display(), err))
+}
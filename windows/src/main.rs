@@ -1,19 +1,42 @@
 // Disable the console window that pops up when you launch the .exe
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod backoff;
+mod benchmark;
 mod cli;
+mod clock;
 mod config;
+mod diagnostics;
+mod exit_policy;
+mod fade;
+mod frame_limiter;
 mod gl_context;
+mod gl_debug;
+#[cfg(test)]
+mod golden;
+mod gpu_timer;
+mod headless;
 mod platform;
+mod quality;
+mod render_target;
 mod settings_window;
 mod surface;
+#[cfg(windows)]
 mod wallpaper;
 mod winit_compat;
 
+use backoff::Backoff;
 use cli::Mode;
-use config::Config;
+use clock::Clock;
+use config::{ColorMode, Config, MonitorPlacement};
+use exit_policy::ExitPolicy;
+use fade::{Dissolve, Fader, Overlay};
 use flux::Flux;
-use winit_compat::{HasMonitors, HasWinitWindow, MonitorHandle};
+use frame_limiter::FrameLimiter;
+use gpu_timer::GpuTimer;
+use quality::QualityController;
+use render_target::RenderTarget;
+use winit_compat::{HasMonitors, HasRotation, HasStableId, HasWinitWindow, MonitorHandle};
 
 use std::collections::HashMap;
 use std::{fs, path, process, rc::Rc};
@@ -43,8 +66,23 @@ pub static mut NvOptimusEnablement: i32 = 1;
 #[no_mangle]
 pub static mut AmdPowerXpressRequestHighPerformance: i32 = 1;
 
-// Higher values will make the screensaver tolerate more mouse movement before exiting.
-const MINIMUM_MOUSE_MOTION_TO_EXIT_SCREENSAVER: f64 = 10.0;
+// Without vsync, cap the frame rate to avoid drawing frames as fast as we can.
+const FALLBACK_MAX_FPS: u32 = 60;
+
+// Adaptive quality aims to render at least this many frames per second, unless the frame rate is
+// capped lower.
+const TARGET_FPS: u32 = 60;
+
+// SDL doesn’t report resolution changes, so we periodically re-check the monitor layout.
+const DISPLAY_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+// Stop rebuilding a window’s renderer after losing the context this many times in a row, as
+// counted by `Backoff`. A driver that keeps resetting would otherwise have us rebuilding forever.
+const MAX_REBUILDS: u32 = 3;
+
+// Once a window draws this many frames in a row, about ten seconds’ worth, forget its earlier
+// context losses. A driver that resets once in a while shouldn’t close the window for good.
+const HEALTHY_FRAMES: u32 = 600;
 
 type WindowId = u32;
 
@@ -54,54 +92,560 @@ struct Instance {
     window: Window,
     gl_context: gl_context::GLContext,
     swapchain: Swapchain,
+    surface: surface::Surface,
+    // The settings before any quality adjustments
+    settings: Rc<flux::settings::Settings>,
+    quality: Option<QualityController>,
+    // The configured render scale, before any quality adjustments
+    render_scale: f32,
+    render_target: Option<RenderTarget>,
+    overlay: Option<Overlay>,
+    // The desktop capture we dissolve from at startup
+    dissolve: Option<Dissolve>,
+    // Times each frame on the GPU while benchmarking
+    gpu_timer: Option<GpuTimer>,
+    // What the OpenGL context renders to, so that we can recreate it
+    target: ContextTarget,
+    // Set when drawing fails in a way that means the driver lost the context
+    context_lost: bool,
+    // The preview in the screensaver settings never fades and keeps a fixed quality.
+    preview: bool,
+    // Minimized or hidden. The clock only stops once every window is out of sight.
+    hidden: bool,
+    // Frames drawn since the context was last rebuilt
+    frames_drawn: u32,
+}
+
+#[derive(Clone, Copy)]
+struct ContextTarget {
+    raw_window_handle: RawWindowHandle,
+    // The window to open the display with, for the preview. See `gl_context::new_gl_context`.
+    attr_window: Option<RawWindowHandle>,
+    // Present through DXGI, if available. The DXGI swapchain can’t follow the window as it
+    // resizes.
+    dxgi: bool,
 }
 
 enum Swapchain {
-    Gl,
+    Gl {
+        vsync: bool,
+    },
 
     #[cfg(windows)]
     Dxgi(platform::windows::dxgi_swapchain::DXGIInterop),
 }
 
 impl Instance {
-    pub fn draw(&mut self, timestamp: f64) {
+    fn new(
+        window: Window,
+        target: ContextTarget,
+        surface: surface::Surface,
+        config: &Config,
+        preview: bool,
+    ) -> Result<Self, String> {
+        let adaptive_quality = config.rendering.adaptive_quality && !surface.blackout && !preview;
+
+        // Blitting the render target onto a multisampled window is an invalid operation, so skip
+        // MSAA whenever we might render at a lower scale.
+        let mut preferences = config.rendering.surface.clone();
+        if preferences.msaa_samples > 0
+            && (config.rendering.render_scale() < 1.0 || adaptive_quality)
+        {
+            log::warn!(
+                "Turning off MSAA, because it doesn’t work with a render scale or adaptive quality"
+            );
+            preferences.msaa_samples = 0;
+        }
+        let preferences = &preferences;
+
+        let new_gl_context = |window: &Window, target: &ContextTarget, relaxed| {
+            gl_context::new_gl_context(
+                window.raw_display_handle(),
+                surface.size,
+                target.raw_window_handle,
+                target.attr_window,
+                preferences,
+                relaxed,
+            )
+        };
+
+        // Some drivers can’t do transparency or an alpha channel, so try again without them.
+        let (window, target, gl_context) = match new_gl_context(&window, &target, false) {
+            Ok(gl_context) => (window, target, gl_context),
+            Err(err) if preferences.require_transparency => {
+                log::warn!("Not relaxing the OpenGL config, because transparency is required");
+                return Err(err.to_string());
+            }
+            Err(err) => {
+                log::warn!("{}. Trying a relaxed config.", err);
+
+                // WGL already set the pixel format of the window, and that can’t change. The
+                // preview draws into a window that belongs to the screensaver dialog, which we
+                // can’t replace.
+                #[cfg(windows)]
+                let (window, target) = {
+                    if target.attr_window.is_some() {
+                        return Err(err.to_string());
+                    }
+
+                    let window = recreate_window(&window)?;
+                    let target = ContextTarget {
+                        raw_window_handle: window.raw_window_handle(),
+                        ..target
+                    };
+                    (window, target)
+                };
+
+                let gl_context =
+                    new_gl_context(&window, &target, true).map_err(|err| err.to_string())?;
+                (window, target, gl_context)
+            }
+        };
+
+        let swapchain = if target.dxgi {
+            create_swapchain(&target.raw_window_handle, &gl_context)
+        } else {
+            Swapchain::Gl {
+                vsync: enable_vsync(&gl_context),
+            }
+        };
+
+        let settings = Rc::new(config.to_settings(&surface));
+        // Adaptive quality goes by how long the GPU takes, measured with timer queries, since
+        // waiting for the GPU on the CPU would slow down the very machines it’s meant to help.
+        let mut quality = adaptive_quality.then(|| QualityController::new(frame_budget(config)));
+        let gpu_timer = quality
+            .is_some()
+            .then(|| GpuTimer::new(&gl_context.gl))
+            .flatten();
+        if quality.is_some() && gpu_timer.is_none() {
+            log::warn!("Timer queries aren’t supported. Turning off adaptive quality.");
+            quality = None;
+        }
+        let render_scale = config.rendering.render_scale();
+        let render_size = scale_size(surface.size, render_scale);
+        let logical_size = surface.size.to_logical(surface.scale_factor);
+        let flux = Flux::new(
+            &gl_context.gl,
+            logical_size.width,
+            logical_size.height,
+            render_size.width,
+            render_size.height,
+            &settings,
+        )
+        .map_err(|err| err.to_string())?;
+
+        let overlay = if config.fade.is_enabled() && !surface.blackout && !preview {
+            Some(Overlay::new(&gl_context.gl)?)
+        } else {
+            None
+        };
+
+        let mut instance = Self {
+            flux,
+            window,
+            gl_context,
+            swapchain,
+            surface,
+            settings,
+            quality,
+            render_scale,
+            render_target: None,
+            overlay,
+            dissolve: None,
+            gpu_timer,
+            target,
+            context_lost: false,
+            preview,
+            hidden: false,
+            frames_drawn: 0,
+        };
+        instance.update_render_target()?;
+
+        Ok(instance)
+    }
+
+    // The fade is the opacity of the black overlay drawn on top of the simulation. The dissolve is
+    // how far the desktop capture, if any, has broken up to reveal the simulation.
+    pub fn draw(&mut self, timestamp: f64, fade: f32, dissolve: f32) -> Result<(), String> {
+        // Don’t query a context that isn’t current. Without robustness, assume it’s lost.
+        if let Err(err) = self.make_current() {
+            self.context_lost = !self.gl_context.can_report_reset();
+            return Err(err);
+        }
+
+        // A lost context ignores everything we draw, so we’d be stuck on a black screen.
+        if self.gl_context.is_lost() {
+            self.context_lost = true;
+            return Err("The graphics driver reset the OpenGL context".to_string());
+        }
+
         match self.swapchain {
-            Swapchain::Gl => {
-                self.gl_context
-                    .context
-                    .make_current(&self.gl_context.surface)
-                    .expect("make OpenGL context current");
+            Swapchain::Gl { .. } => {
+                if let Some(gpu_timer) = &mut self.gpu_timer {
+                    gpu_timer.begin(&self.gl_context.gl);
+                }
+
+                if self.surface.blackout {
+                    clear_to_black(&self.gl_context.gl);
+                } else {
+                    render_frame(
+                        &mut self.flux,
+                        &self.gl_context.gl,
+                        self.render_target.as_ref(),
+                        None,
+                        self.surface.size,
+                        timestamp,
+                    );
+                }
 
-                self.flux.animate(timestamp);
+                if let Some(desktop) = &self.dissolve {
+                    desktop.draw(&self.gl_context.gl, None, self.surface.size, dissolve);
+                }
+
+                if let Some(overlay) = &self.overlay {
+                    overlay.draw(&self.gl_context.gl, None, self.surface.size, fade);
+                }
 
-                self.gl_context
+                if let Some(gpu_timer) = &mut self.gpu_timer {
+                    gpu_timer.end(&self.gl_context.gl);
+                }
+
+                if let Err(err) = self
+                    .gl_context
                     .surface
                     .swap_buffers(&self.gl_context.context)
-                    .expect("swap OpenGL buffers");
+                {
+                    self.context_lost = self.gl_context.is_lost_after_failure();
+                    return Err(format!("Failed to swap the OpenGL buffers: {}", err));
+                }
             }
 
             #[cfg(windows)]
             Swapchain::Dxgi(ref mut dxgi_interop) => unsafe {
-                platform::windows::dxgi_swapchain::with_dxgi_swapchain(dxgi_interop, |fbo| {
-                    self.gl_context
-                        .context
-                        .make_current(&self.gl_context.surface)
-                        .expect("make OpenGL context current");
+                let presented =
+                    platform::windows::dxgi_swapchain::with_dxgi_swapchain(dxgi_interop, |fbo| {
+                        if let Some(gpu_timer) = &mut self.gpu_timer {
+                            gpu_timer.begin(&self.gl_context.gl);
+                        }
+
+                        if self.surface.blackout {
+                            self.gl_context
+                                .gl
+                                .bind_framebuffer(GL::FRAMEBUFFER, Some(*fbo));
+                            clear_to_black(&self.gl_context.gl);
+                        } else {
+                            render_frame(
+                                &mut self.flux,
+                                &self.gl_context.gl,
+                                self.render_target.as_ref(),
+                                Some(*fbo),
+                                self.surface.size,
+                                timestamp,
+                            );
+                        }
+
+                        if let Some(desktop) = &self.dissolve {
+                            desktop.draw(
+                                &self.gl_context.gl,
+                                Some(*fbo),
+                                self.surface.size,
+                                dissolve,
+                            );
+                        }
+
+                        if let Some(overlay) = &self.overlay {
+                            overlay.draw(&self.gl_context.gl, Some(*fbo), self.surface.size, fade);
+                        }
+
+                        if let Some(gpu_timer) = &mut self.gpu_timer {
+                            gpu_timer.end(&self.gl_context.gl);
+                        }
+
+                        self.gl_context.gl.bind_framebuffer(GL::FRAMEBUFFER, None);
+                        self.gl_context.gl.finish();
+                    });
+
+                // This is also where a removed device shows up.
+                if let Err(err) = presented {
+                    self.context_lost = platform::windows::dxgi_swapchain::is_device_lost(&err)
+                        || self.gl_context.is_lost_after_failure();
+                    return Err(format!("Failed to present the DXGI swapchain: {}", err));
+                }
+            },
+        }
 
-                    self.flux.compute(timestamp);
+        // The context is still current from drawing the frame.
+        if dissolve >= 1.0 {
+            if let Some(desktop) = self.dissolve.take() {
+                desktop.delete(&self.gl_context.gl);
+            }
+        }
 
-                    self.gl_context
-                        .gl
-                        .bind_framebuffer(GL::FRAMEBUFFER, Some(*fbo));
+        self.frames_drawn = self.frames_drawn.saturating_add(1);
 
-                    self.flux.render();
+        if self.quality.is_some() {
+            let render_times = match &mut self.gpu_timer {
+                Some(gpu_timer) => {
+                    gpu_timer.collect(&self.gl_context.gl, false);
+                    gpu_timer.take_samples()
+                }
+                None => Vec::new(),
+            };
 
-                    self.gl_context.gl.bind_framebuffer(GL::FRAMEBUFFER, None);
-                    self.gl_context.gl.finish();
-                });
-            },
+            for render_time in render_times {
+                self.adapt_quality(render_time);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn make_current(&self) -> Result<(), String> {
+        self.gl_context
+            .context
+            .make_current(&self.gl_context.surface)
+            .map_err(|err| format!("Failed to make the OpenGL context current: {}", err))
+    }
+
+    // Recreate the context, the swapchain, and the simulation in the same window, after the
+    // driver lost the context. Gives up if the context keeps getting lost.
+    pub fn rebuild(
+        self,
+        config: &Config,
+        rebuilds: &mut Backoff<surface::Bounds>,
+    ) -> Result<Self, String> {
+        let lost = rebuilds.fail(self.surface.bounds(), std::time::Instant::now());
+        if lost > MAX_REBUILDS {
+            return Err(format!(
+                "Lost the OpenGL context {} times in a row. Giving up.",
+                lost
+            ));
+        }
+
+        let Instance {
+            flux,
+            window,
+            gl_context,
+            swapchain,
+            surface,
+            render_target,
+            overlay,
+            dissolve,
+            gpu_timer,
+            target,
+            preview,
+            hidden,
+            ..
+        } = self;
+
+        // Tear down the old context first. Windows only allow a single swapchain or EGL surface.
+        // The GL objects went down with the context, so there’s nothing to delete.
+        drop((flux, render_target, overlay, dissolve, gpu_timer, swapchain));
+        drop(gl_context);
+
+        let mut instance = Instance::new(window, target, surface, config, preview)?;
+        instance.hidden = hidden;
+
+        Ok(instance)
+    }
+
+    // Reset the rebuild count once the window has been drawing fine for a while.
+    fn forget_rebuilds_when_healthy(&self, rebuilds: &mut Backoff<surface::Bounds>) {
+        if self.frames_drawn == HEALTHY_FRAMES {
+            rebuilds.succeed(&self.surface.bounds());
+        }
+    }
+
+    // Start from a capture of the desktop, which dissolves into the simulation.
+    pub fn set_dissolve(&mut self, screenshot: &platform::Screenshot) -> Result<(), String> {
+        self.make_current()?;
+        self.dissolve = Some(Dissolve::new(&self.gl_context.gl, screenshot)?);
+
+        Ok(())
+    }
+
+    fn adapt_quality(&mut self, render_time: std::time::Duration) {
+        let Some(quality) = self
+            .quality
+            .as_mut()
+            .and_then(|controller| controller.record(render_time))
+        else {
+            return;
+        };
+
+        log::debug!("Changing quality to {:?}", quality);
+
+        if let Err(err) = self.apply_settings() {
+            log::error!("Failed to change the quality: {}", err);
+        }
+    }
+
+    // Update the simulation in place with the settings for the surface, adjusted for the current
+    // quality, so that the fluid carries on where it was.
+    fn apply_settings(&mut self) -> Result<(), String> {
+        let mut settings = (*self.settings).clone();
+        if let Some(controller) = &self.quality {
+            controller.quality().apply(&mut settings);
         }
+
+        self.make_current()?;
+        self.flux.update(&Rc::new(settings));
+
+        // The quality also sets the render scale.
+        let render_size = self.render_size();
+        let logical_size = self.surface.size.to_logical(self.surface.scale_factor);
+        self.flux.resize(
+            logical_size.width,
+            logical_size.height,
+            render_size.width,
+            render_size.height,
+        );
+
+        self.update_render_target()
     }
+
+    // Move and resize the window, and the simulation, to fit a new surface.
+    pub fn resize(&mut self, config: &Config, surface: &surface::Surface) -> Result<(), String> {
+        use sdl2::video::WindowPos;
+
+        self.window.set_position(
+            WindowPos::Positioned(surface.position.x),
+            WindowPos::Positioned(surface.position.y),
+        );
+        self.window
+            .set_size(surface.size.width, surface.size.height)
+            .map_err(|err| err.to_string())?;
+
+        self.resize_surface(config, surface)
+    }
+
+    // Resize the simulation to fit the window, after the user resized it.
+    pub fn fit_to_window(&mut self, config: &Config) -> Result<(), String> {
+        let (width, height) = self.window.drawable_size();
+        let surface = surface::Surface {
+            size: PhysicalSize::new(width, height),
+            ..self.surface.clone()
+        };
+
+        self.resize_surface(config, &surface)
+    }
+
+    fn resize_surface(
+        &mut self,
+        config: &Config,
+        surface: &surface::Surface,
+    ) -> Result<(), String> {
+        use winit_compat::NonZeroU32PhysicalSize;
+
+        let (width, height) = surface
+            .size
+            .non_zero()
+            .ok_or("Can’t resize a window to a zero size")?;
+
+        self.make_current()?;
+        self.gl_context
+            .surface
+            .resize(&self.gl_context.context, width, height);
+
+        // The planned surfaces don’t look up wallpapers, so keep the one we started with.
+        self.surface = surface::Surface {
+            wallpaper: self.surface.wallpaper.take(),
+            ..surface.clone()
+        };
+        // The settings depend on the shape of the surface, like a monitor rotated into portrait.
+        self.settings = Rc::new(config.to_settings(&self.surface));
+
+        self.apply_settings()
+    }
+
+    // The render scale, including any reduction from adaptive quality.
+    fn effective_render_scale(&self) -> f32 {
+        let quality_scale = self
+            .quality
+            .as_ref()
+            .map_or(1.0, |controller| controller.quality().render_scale);
+
+        (self.render_scale * quality_scale).max(config::MIN_RENDER_SCALE)
+    }
+
+    // The size of the framebuffer we render the simulation into.
+    fn render_size(&self) -> PhysicalSize<u32> {
+        scale_size(self.surface.size, self.effective_render_scale())
+    }
+
+    // Recreate the offscreen render target to match the current render size. We only need one if
+    // we’re rendering at a lower resolution than the window.
+    fn update_render_target(&mut self) -> Result<(), String> {
+        let render_size = self.render_size();
+
+        if let Some(render_target) = self.render_target.take() {
+            render_target.delete(&self.gl_context.gl);
+        }
+
+        if render_size != self.surface.size {
+            self.render_target = Some(RenderTarget::new(&self.gl_context.gl, render_size)?);
+        }
+
+        Ok(())
+    }
+
+    // The DXGI swapchain is tied to the size of the window it was created for.
+    fn can_resize(&self) -> bool {
+        matches!(self.swapchain, Swapchain::Gl { .. })
+    }
+
+    fn swapchain_name(&self) -> &'static str {
+        match self.swapchain {
+            Swapchain::Gl { .. } => "gl",
+
+            #[cfg(windows)]
+            Swapchain::Dxgi(_) => "dxgi",
+        }
+    }
+
+    fn has_vsync(&self) -> bool {
+        match self.swapchain {
+            Swapchain::Gl { vsync } => vsync,
+
+            #[cfg(windows)]
+            Swapchain::Dxgi(ref dxgi_interop) => dxgi_interop.vsync,
+        }
+    }
+}
+
+// Draw a frame of the simulation into `framebuffer`, or the default framebuffer if `None`. With a
+// render target, we render into the render target first and then upscale.
+fn render_frame(
+    flux: &mut Flux,
+    gl: &glow::Context,
+    render_target: Option<&RenderTarget>,
+    framebuffer: Option<GL::NativeFramebuffer>,
+    size: PhysicalSize<u32>,
+    timestamp: f64,
+) {
+    flux.compute(timestamp);
+
+    unsafe {
+        match render_target {
+            Some(render_target) => {
+                gl.bind_framebuffer(GL::FRAMEBUFFER, Some(render_target.framebuffer()));
+                flux.render();
+                render_target.blit(gl, framebuffer, size);
+            }
+            None => {
+                gl.bind_framebuffer(GL::FRAMEBUFFER, framebuffer);
+                flux.render();
+            }
+        }
+    }
+}
+
+fn scale_size(size: PhysicalSize<u32>, scale: f32) -> PhysicalSize<u32> {
+    PhysicalSize::new(
+        ((size.width as f32 * scale).round() as u32).max(1),
+        ((size.height as f32 * scale).round() as u32).max(1),
+    )
 }
 
 fn main() {
@@ -112,6 +656,7 @@ fn main() {
     init_logging(log_dir);
 
     let config = Config::load(config_dir);
+    log::set_max_level(config.log_level.to_level_filter());
 
     match cli::read_flags().and_then(|mode| {
         if mode == Mode::Settings {
@@ -121,6 +666,10 @@ fn main() {
             return Ok(());
         }
 
+        // Only apply the overrides to this run, so they never end up in the saved settings.
+        let mut config = config;
+        cli::read_overrides()?.apply(&mut config);
+
         run_flux(mode, config)
     }) {
         Ok(_) => process::exit(0),
@@ -152,8 +701,9 @@ fn init_logging(optional_log_dir: Option<&path::Path>) {
         };
 
         if let Ok(log_file) = maybe_log_file {
+            // The log level from the settings decides what reaches the file.
             loggers.push(WriteLogger::new(
-                LevelFilter::Warn,
+                LevelFilter::Trace,
                 Config::default(),
                 log_file,
             ));
@@ -161,10 +711,16 @@ fn init_logging(optional_log_dir: Option<&path::Path>) {
     }
 
     let _ = CombinedLogger::init(loggers);
+    log::set_max_level(LevelFilter::Warn);
     log_panics::init();
 }
 
-fn run_flux(mode: Mode, config: Config) -> Result<(), String> {
+fn run_flux(mode: Mode, mut config: Config) -> Result<(), String> {
+    // Recording shouldn’t touch the display at all.
+    if let Mode::Record(options) = &mode {
+        return headless::run(&config, options);
+    }
+
     #[cfg(windows)]
     platform::windows::dpi_awareness::set_dpi_awareness()?;
 
@@ -176,34 +732,124 @@ fn run_flux(mode: Mode, config: Config) -> Result<(), String> {
     let video_subsystem = sdl_context.video()?;
 
     match mode {
+        Mode::ListMonitors => {
+            #[cfg(windows)]
+            platform::windows::console::attach_parent_console();
+
+            for monitor in video_subsystem.available_monitors() {
+                let id = monitor.stable_id();
+                let placement = config.monitors.placement(&id);
+                let size = monitor.size();
+                let position = monitor.position();
+
+                println!(
+                    "{id}\n  Name: {}\n  Size: {}x{} at ({}, {})\n  Scale factor: {}\n  Rotation: {:?}\n  Placement: {placement}",
+                    monitor.name().unwrap_or_default(),
+                    size.width,
+                    size.height,
+                    position.x,
+                    position.y,
+                    monitor.scale_factor(),
+                    monitor.rotation(),
+                );
+            }
+
+            Ok(())
+        }
+
+        Mode::Benchmark(options) => {
+            #[cfg(windows)]
+            platform::windows::console::attach_parent_console();
+
+            benchmark::run(&sdl_context, &video_subsystem, config, &options)
+        }
+
+        Mode::Diagnostics(options) => {
+            #[cfg(windows)]
+            platform::windows::console::attach_parent_console();
+
+            diagnostics::run(&video_subsystem, config, &options)
+        }
+
+        #[cfg(windows)]
         Mode::Preview(raw_window_handle) => {
-            #[cfg(not(windows))]
-            panic!("Preview window unsupported");
+            let instance = new_preview_window(&video_subsystem, raw_window_handle, &config)?;
+            let mut clock = Clock::from_settings(&config.clock);
+            let mut event_pump = sdl_context.event_pump()?;
 
-            let mut instance = new_preview_window(&video_subsystem, raw_window_handle, &config)?;
-            let start = std::time::Instant::now();
+            run_preview_loop(&mut event_pump, &config, instance, &mut clock)
+        }
+
+        #[cfg(not(windows))]
+        Mode::Preview(_) => Err(
+            "The preview window is only supported on Windows. Try --window instead.".to_string(),
+        ),
+
+        Mode::Window(size) => {
+            let mut instance = new_windowed_instance(&video_subsystem, &config, size)?;
+            instance.window.show();
+
+            let mut clock = Clock::from_settings(&config.clock);
             let mut event_pump = sdl_context.event_pump()?;
 
-            run_preview_loop(&mut event_pump, &mut instance, start)
+            run_window_loop(&mut event_pump, &config, instance, &mut clock)
         }
 
-        Mode::Screensaver => {
-            let monitors = video_subsystem
-                .available_monitors()
-                .map(|monitor| (monitor.clone(), wallpaper::get(&monitor).ok()))
-                .collect::<Vec<(MonitorHandle, Option<std::path::PathBuf>)>>();
-            log::debug!("Available monitors: {:?}", monitors);
+        Mode::Screensaver | Mode::Kiosk => {
+            if mode == Mode::Kiosk {
+                config.kiosk.enabled = true;
+            }
 
-            let surfaces = surface::combine_monitors(&monitors);
+            config.resolve_seed();
+            if let Some(seed) = &config.flux.seed {
+                log::info!("Seed: {}", seed);
+            }
+
+            let surfaces = plan_surfaces(&video_subsystem, &config);
             log::debug!("Creating windows: {:?}", surfaces);
 
-            let mut instances = surfaces
+            // Capture the desktop before any of our windows cover it.
+            let screenshots = surfaces
                 .iter()
                 .map(|surface| {
-                    new_instance(&video_subsystem, &config, surface)
-                        .map(|instance| (instance.window.id(), instance))
+                    if !config.fade.dissolve_desktop || surface.blackout {
+                        return None;
+                    }
+
+                    platform::capture_screen(surface.position, surface.size)
+                        .map_err(|err| log::warn!("Failed to capture the desktop: {}", err))
+                        .ok()
+                })
+                .collect::<Vec<Option<platform::Screenshot>>>();
+
+            // Skip the monitors we can’t draw on, instead of giving up on all of them.
+            let mut instances = surfaces
+                .iter()
+                .zip(screenshots.iter())
+                .filter_map(|(surface, screenshot)| {
+                    let mut instance = match new_instance(&video_subsystem, &config, surface, true)
+                    {
+                        Ok(instance) => instance,
+                        Err(err) => {
+                            log::error!("Failed to create a window for {:?}: {}", surface, err);
+                            return None;
+                        }
+                    };
+
+                    if let Some(screenshot) = screenshot {
+                        if let Err(err) = instance.set_dissolve(screenshot) {
+                            log::warn!("Failed to set up the desktop dissolve: {}", err);
+                        }
+                    }
+
+                    Some((instance.window.id(), instance))
                 })
-                .collect::<Result<HashMap<WindowId, Instance>, String>>()?;
+                .collect::<HashMap<WindowId, Instance>>();
+
+            // In kiosk mode, the layout checks keep retrying the missing windows.
+            if instances.is_empty() && !surfaces.is_empty() && !config.kiosk.enabled {
+                return Err("Failed to create a window on any of the monitors".to_string());
+            }
 
             // Hide the cursor and report relative mouse movements.
             sdl_context.mouse().set_relative_mouse_mode(true);
@@ -214,22 +860,193 @@ fn run_flux(mode: Mode, config: Config) -> Result<(), String> {
             }
 
             let mut event_pump = sdl_context.event_pump()?;
-            let start = std::time::Instant::now();
-
-            run_main_loop(&mut event_pump, &mut instances, start)
+            let mut clock = Clock::from_settings(&config.clock);
+
+            run_main_loop(
+                &mut event_pump,
+                &video_subsystem,
+                &config,
+                &mut instances,
+                &mut clock,
+            )
         }
 
         _ => unreachable!(),
     }
 }
 
+fn plan_surfaces(video_subsystem: &sdl2::VideoSubsystem, config: &Config) -> Vec<surface::Surface> {
+    let monitors = video_subsystem
+        .available_monitors()
+        .filter_map(
+            |monitor| match config.monitors.placement(&monitor.stable_id()) {
+                MonitorPlacement::Exclude => None,
+                placement => Some((monitor, placement == MonitorPlacement::Blackout)),
+            },
+        )
+        .collect::<Vec<(MonitorHandle, bool)>>();
+    log::debug!("Available monitors: {:?}", monitors);
+
+    surface::combine_monitors(&monitors)
+}
+
+// Bring the instances in line with the current monitor layout. Surfaces whose windows keep
+// failing are skipped until their backoff runs out.
+fn update_instances(
+    video_subsystem: &sdl2::VideoSubsystem,
+    config: &Config,
+    instances: &mut HashMap<WindowId, Instance>,
+    retries: &mut Backoff<surface::Bounds>,
+) {
+    let existing = instances
+        .iter()
+        .map(|(id, instance)| (*id, instance.surface.clone()))
+        .collect::<Vec<(WindowId, surface::Surface)>>();
+    let diff = surface::diff_surfaces(&existing, &plan_surfaces(video_subsystem, config));
+
+    if diff.is_empty() {
+        return;
+    }
+
+    log::debug!("Monitor layout changed: {:?}", diff);
+
+    let mut created = diff.created;
+
+    for id in diff.removed {
+        instances.remove(&id);
+    }
+
+    for (id, surface) in diff.resized {
+        let resized = match instances.get_mut(&id) {
+            Some(instance) if instance.can_resize() => instance.resize(config, &surface),
+            _ => Err("Can’t resize the window in place".to_string()),
+        };
+
+        if let Err(err) = resized {
+            log::debug!("{}. Recreating the window.", err);
+            instances.remove(&id);
+            created.push(surface);
+        }
+    }
+
+    let now = std::time::Instant::now();
+
+    for surface in created {
+        if retries.is_waiting(&surface.bounds(), now) {
+            log::debug!("Waiting before retrying the window for {:?}", surface);
+            continue;
+        }
+
+        match new_instance(video_subsystem, config, &surface, true) {
+            Ok(mut instance) => {
+                instance.window.show();
+                instances.insert(instance.window.id(), instance);
+            }
+            Err(err) => {
+                let failures = retries.fail(surface.bounds(), now);
+                log::error!(
+                    "Failed to create a window for {:?} ({} in a row): {}",
+                    surface,
+                    failures,
+                    err
+                );
+            }
+        }
+    }
+}
+
+fn max_fps<'a>(config: &Config, mut instances: impl Iterator<Item = &'a Instance>) -> Option<u32> {
+    config.rendering.max_fps.or_else(|| {
+        instances
+            .any(|instance| !instance.has_vsync())
+            .then_some(FALLBACK_MAX_FPS)
+    })
+}
+
+fn frame_budget(config: &Config) -> std::time::Duration {
+    let fps = config
+        .rendering
+        .max_fps
+        .filter(|fps| *fps > 0)
+        .map_or(TARGET_FPS, |fps| fps.min(TARGET_FPS));
+
+    std::time::Duration::from_secs_f64(1.0 / fps as f64)
+}
+
+// Keep the display awake during the configured hours, and let it sleep otherwise.
+fn update_keep_awake(
+    video_subsystem: &sdl2::VideoSubsystem,
+    config: &Config,
+    keep_awake: &mut bool,
+) {
+    let Some(awake_hours) = &config.kiosk.awake_hours else {
+        return;
+    };
+
+    let Some(minutes) = platform::local_minutes_since_midnight() else {
+        log::warn!("Can’t read the local time. Letting the display sleep.");
+        return;
+    };
+
+    let should_keep_awake = match awake_hours.contains(minutes) {
+        Ok(contains) => contains,
+        Err(err) => {
+            log::error!("{}", err);
+            false
+        }
+    };
+
+    if should_keep_awake == *keep_awake {
+        return;
+    }
+
+    if should_keep_awake {
+        log::info!("Keeping the display awake");
+        video_subsystem.disable_screen_saver();
+    } else {
+        log::info!("Letting the display sleep");
+        video_subsystem.enable_screen_saver();
+    }
+
+    *keep_awake = should_keep_awake;
+}
+
+// Track whether a window is out of sight. Returns whether the window was shown or hidden.
+fn update_visibility(instance: &mut Instance, win_event: &sdl2::event::WindowEvent) -> bool {
+    use sdl2::event::WindowEvent;
+
+    match win_event {
+        WindowEvent::Minimized | WindowEvent::Hidden => instance.hidden = true,
+        WindowEvent::Restored | WindowEvent::Shown => instance.hidden = false,
+        _ => return false,
+    }
+
+    true
+}
+
+// Stop the simulation while nothing can see it. Time keeps going while any window is visible,
+// so that the ones on screen don’t freeze because another one was minimized. With no windows
+// at all, the clock waits for the displays to come back.
+fn pause_when_hidden<'a>(clock: &mut Clock, mut instances: impl Iterator<Item = &'a Instance>) {
+    if instances.all(|instance| instance.hidden) {
+        clock.pause();
+    } else {
+        clock.resume();
+    }
+}
+
+#[cfg(windows)]
 fn run_preview_loop(
     event_pump: &mut sdl2::EventPump,
-    instance: &mut Instance,
-    start: std::time::Instant,
+    config: &Config,
+    mut instance: Instance,
+    clock: &mut Clock,
 ) -> Result<(), String> {
     use sdl2::event::Event;
 
+    let mut frame_limiter = FrameLimiter::new(max_fps(config, std::iter::once(&instance)));
+    let mut rebuilds = Backoff::new();
+
     'main: loop {
         for event in event_pump.poll_iter() {
             match event {
@@ -239,12 +1056,87 @@ fn run_preview_loop(
                     ..
                 } => break 'main,
 
+                Event::Window { win_event, .. } => {
+                    if update_visibility(&mut instance, &win_event) {
+                        pause_when_hidden(clock, std::iter::once(&instance));
+                    }
+                }
+
+                _ => (),
+            }
+        }
+
+        match instance.draw(clock.tick(), 0.0, 1.0) {
+            Ok(()) => instance.forget_rebuilds_when_healthy(&mut rebuilds),
+            Err(err) if instance.context_lost => {
+                log::warn!("{}. Rebuilding the renderer.", err);
+                instance = instance.rebuild(config, &mut rebuilds)?;
+                frame_limiter.set_max_fps(max_fps(config, std::iter::once(&instance)));
+            }
+            Err(err) => return Err(err),
+        }
+
+        frame_limiter.wait();
+    }
+
+    Ok(())
+}
+
+fn run_window_loop(
+    event_pump: &mut sdl2::EventPump,
+    config: &Config,
+    mut instance: Instance,
+    clock: &mut Clock,
+) -> Result<(), String> {
+    use sdl2::event::{Event, WindowEvent};
+    use sdl2::keyboard::Keycode;
+
+    let mut frame_limiter = FrameLimiter::new(max_fps(config, std::iter::once(&instance)));
+    let mut rebuilds = Backoff::new();
+
+    'main: loop {
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::Window {
+                    win_event: WindowEvent::Close,
+                    ..
+                }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => break 'main,
+
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(..),
+                    ..
+                } => {
+                    if let Err(err) = instance.fit_to_window(config) {
+                        log::warn!("Failed to resize: {}", err);
+                    }
+                }
+
+                Event::Window { win_event, .. } => {
+                    if update_visibility(&mut instance, &win_event) {
+                        pause_when_hidden(clock, std::iter::once(&instance));
+                    }
+                }
+
                 _ => (),
             }
         }
 
-        let timestamp = start.elapsed().as_secs_f64() * 1000.0;
-        instance.draw(timestamp);
+        match instance.draw(clock.tick(), 0.0, 1.0) {
+            Ok(()) => instance.forget_rebuilds_when_healthy(&mut rebuilds),
+            Err(err) if instance.context_lost => {
+                log::warn!("{}. Rebuilding the renderer.", err);
+                instance = instance.rebuild(config, &mut rebuilds)?;
+                frame_limiter.set_max_fps(max_fps(config, std::iter::once(&instance)));
+            }
+            Err(err) => return Err(err),
+        }
+
+        frame_limiter.wait();
     }
 
     Ok(())
@@ -252,29 +1144,62 @@ fn run_preview_loop(
 
 fn run_main_loop(
     event_pump: &mut sdl2::EventPump,
+    video_subsystem: &sdl2::VideoSubsystem,
+    config: &Config,
     instances: &mut HashMap<WindowId, Instance>,
-    start: std::time::Instant,
+    clock: &mut Clock,
 ) -> Result<(), String> {
     use sdl2::event::Event;
 
+    let mut last_display_check = std::time::Instant::now();
+    let mut frame_limiter = FrameLimiter::new(max_fps(config, instances.values()));
+    let mut fader = Fader::new(
+        config.fade.fade_in(),
+        config.fade.fade_out(),
+        config.fade.dissolve(),
+    );
+    let mut exit_policy = ExitPolicy::new(&config.exit, std::time::Instant::now());
+    let kiosk = config.kiosk.enabled;
+    let mut keep_awake = false;
+    let mut restart_windows = false;
+    let mut retries = Backoff::new();
+    let mut rebuilds = Backoff::new();
+
+    if kiosk {
+        update_keep_awake(video_subsystem, config, &mut keep_awake);
+    }
+
     'main: loop {
+        let mut displays_changed =
+            restart_windows || last_display_check.elapsed() > DISPLAY_POLL_INTERVAL;
+        restart_windows = false;
+
         for event in event_pump.poll_iter() {
+            let now = std::time::Instant::now();
+
+            if !kiosk && exit_policy.handle(&event, now) && fader.fade_out(now) {
+                break 'main;
+            }
+
             match event {
                 Event::Quit { .. }
                 | Event::Window {
                     win_event: sdl2::event::WindowEvent::Close,
                     ..
-                }
-                | Event::KeyDown { .. }
-                | Event::MouseButtonDown { .. } => {
+                } => {
                     break 'main;
                 }
 
-                Event::MouseMotion { xrel, yrel, .. } => {
-                    if f64::max(xrel.abs() as f64, yrel.abs() as f64)
-                        > MINIMUM_MOUSE_MOTION_TO_EXIT_SCREENSAVER
-                    {
-                        break 'main;
+                Event::Display { .. } => displays_changed = true,
+
+                // Destroying a window also sends Hidden, so ignore the ones we’ve let go of.
+                Event::Window {
+                    window_id,
+                    win_event,
+                    ..
+                } => {
+                    if let Some(instance) = instances.get_mut(&window_id) {
+                        update_visibility(instance, &win_event);
                     }
                 }
 
@@ -282,15 +1207,145 @@ fn run_main_loop(
             }
         }
 
-        for (_, instance) in instances.iter_mut() {
-            let timestamp = start.elapsed().as_secs_f64() * 1000.0;
-            instance.draw(timestamp);
+        if displays_changed {
+            update_instances(video_subsystem, config, instances, &mut retries);
+            frame_limiter.set_max_fps(max_fps(config, instances.values()));
+            last_display_check = std::time::Instant::now();
+
+            if kiosk {
+                update_keep_awake(video_subsystem, config, &mut keep_awake);
+            }
+        }
+
+        let now = std::time::Instant::now();
+
+        if exit_policy.poll(now) && fader.fade_out(now) {
+            break 'main;
+        }
+
+        // Windows come and go with the layout checks and failures, so check on every frame.
+        pause_when_hidden(clock, instances.values());
+
+        // Use the same timestamp for every instance, so that they don’t drift apart by the time
+        // it takes to draw the instances before them.
+        let timestamp = clock.tick();
+        let fade = fader.opacity(now);
+        let dissolve = fader.dissolve_progress(now);
+
+        let mut failed = Vec::new();
+        let mut lost = Vec::new();
+        let mut rebuild_error = None;
+
+        for (id, instance) in instances.iter_mut() {
+            match instance.draw(timestamp, fade, dissolve) {
+                Ok(()) => instance.forget_rebuilds_when_healthy(&mut rebuilds),
+                Err(err) if instance.context_lost => {
+                    log::warn!("{}. Rebuilding the renderer.", err);
+                    lost.push(*id);
+                }
+                Err(err) if !kiosk => return Err(err),
+                Err(err) => {
+                    log::error!("{}. Restarting the window.", err);
+                    failed.push(*id);
+                }
+            }
+        }
+
+        // Give the windows that lost their context a new one. The simulation starts over, but
+        // keeps the same clock.
+        for id in lost {
+            let Some(instance) = instances.remove(&id) else {
+                continue;
+            };
+
+            let bounds = instance.surface.bounds();
+
+            // Like a window that fails to open, drop only this one. The layout checks try it again
+            // later, backing off if it keeps failing.
+            match instance.rebuild(config, &mut rebuilds) {
+                Ok(instance) => {
+                    instances.insert(instance.window.id(), instance);
+                }
+                Err(err) => {
+                    log::error!(
+                        "Failed to rebuild the renderer: {}. Closing the window.",
+                        err
+                    );
+                    retries.fail(bounds, std::time::Instant::now());
+                    restart_windows = true;
+                    rebuild_error = Some(err);
+                }
+            }
+
+            frame_limiter.set_max_fps(max_fps(config, instances.values()));
+        }
+
+        // Outside of kiosk mode, there’s no point in running without anything on screen.
+        if let Some(err) = rebuild_error {
+            if instances.is_empty() && !kiosk {
+                return Err(err);
+            }
+        }
+
+        // Drop the broken windows. The layout checks recreate them from scratch, backing off if
+        // they keep failing.
+        for id in failed {
+            if let Some(instance) = instances.remove(&id) {
+                retries.fail(instance.surface.bounds(), now);
+            }
+            restart_windows = true;
+        }
+
+        if fader.is_faded_out(now) {
+            break 'main;
+        }
+
+        // With no windows to draw, there’s nothing to limit the frame rate. Wait for the displays
+        // to come back instead of spinning.
+        if instances.is_empty() {
+            std::thread::sleep(DISPLAY_POLL_INTERVAL / 4);
+        } else {
+            frame_limiter.wait();
         }
     }
 
     Ok(())
 }
 
+#[cfg(windows)]
+fn monitor_wallpaper(monitor: &MonitorHandle) -> Option<std::path::PathBuf> {
+    wallpaper::get(monitor).ok()
+}
+
+#[cfg(not(windows))]
+fn monitor_wallpaper(_monitor: &MonitorHandle) -> Option<std::path::PathBuf> {
+    None
+}
+
+// The wallpaper of the first monitor the surface covers. Looking it up goes through COM on
+// Windows, so only do it for new windows, and only if the colors come from the wallpaper.
+fn surface_wallpaper(
+    video_subsystem: &sdl2::VideoSubsystem,
+    config: &Config,
+    surface: &surface::Surface,
+) -> Option<std::path::PathBuf> {
+    if config.flux.color_mode != ColorMode::DesktopImage {
+        return None;
+    }
+
+    video_subsystem
+        .available_monitors()
+        .find(|monitor| surface.contains(monitor.position()))
+        .and_then(|monitor| monitor_wallpaper(&monitor))
+}
+
+fn clear_to_black(gl: &glow::Context) {
+    unsafe {
+        gl.clear_color(0.0, 0.0, 0.0, 1.0);
+        gl.clear(GL::COLOR_BUFFER_BIT);
+    }
+}
+
 #[cfg(windows)]
 fn new_preview_window(
     video_subsystem: &sdl2::VideoSubsystem,
@@ -340,98 +1395,131 @@ fn new_preview_window(
         _ => (),
     }
 
-    let gl_context = gl_context::new_gl_context(
-        window.raw_display_handle(),
-        inner_size,
+    let target = ContextTarget {
         raw_window_handle,
-        Some(window.raw_window_handle()),
-    );
-
-    let swapchain = create_swapchain(&raw_window_handle, &gl_context);
+        attr_window: Some(window.raw_window_handle()),
+        dxgi: true,
+    };
 
-    let wallpaper = window
-        .current_monitor()
-        .and_then(|monitor| wallpaper::get(&monitor).ok());
+    let monitor = window.current_monitor();
+    let wallpaper = monitor.as_ref().and_then(monitor_wallpaper);
 
     let physical_size = window.inner_size();
     let scale_factor = window.scale_factor();
-    let logical_size = physical_size.to_logical(scale_factor);
-    let settings = config.to_settings(wallpaper);
-    let flux = Flux::new(
-        &gl_context.gl,
-        logical_size.width,
-        logical_size.height,
-        physical_size.width,
-        physical_size.height,
-        &Rc::new(settings),
-    )
-    .map_err(|err| err.to_string())?;
+    let surface = surface::Surface {
+        position: winit::dpi::PhysicalPosition::new(0, 0),
+        size: physical_size,
+        scale_factor,
+        rotation: monitor
+            .map(|monitor| monitor.rotation())
+            .unwrap_or_default(),
+        wallpaper,
+        blackout: false,
+    };
+    Instance::new(window, target, surface, config, true)
+}
 
-    Ok(Instance {
-        flux,
-        gl_context,
-        window,
-        swapchain,
-    })
+fn new_windowed_instance(
+    video_subsystem: &sdl2::VideoSubsystem,
+    config: &Config,
+    size: PhysicalSize<u32>,
+) -> Result<Instance, String> {
+    let window = video_subsystem
+        .window("Flux", size.width, size.height)
+        .position_centered()
+        .resizable()
+        .hidden()
+        .allow_highdpi()
+        .build()
+        .map_err(|err| err.to_string())?;
+
+    let (width, height) = window.drawable_size();
+    let physical_size = PhysicalSize::new(width, height);
+    let monitor = window.current_monitor();
+    let surface = surface::Surface {
+        position: winit::dpi::PhysicalPosition::new(0, 0),
+        size: physical_size,
+        scale_factor: window.scale_factor(),
+        rotation: Default::default(),
+        wallpaper: monitor.as_ref().and_then(monitor_wallpaper),
+        blackout: false,
+    };
+
+    // The DXGI swapchain can’t follow the window as it resizes.
+    let target = ContextTarget {
+        raw_window_handle: window.raw_window_handle(),
+        attr_window: None,
+        dxgi: false,
+    };
+
+    Instance::new(window, target, surface, config, false)
 }
 
+// With `grab_input`, keep the mouse inside the window, so that it can’t wander off to a
+// different monitor.
 fn new_instance(
     video_subsystem: &sdl2::VideoSubsystem,
     config: &Config,
     surface: &surface::Surface,
+    grab_input: bool,
 ) -> Result<Instance, String> {
     // Create the SDL window
-    let window = video_subsystem
-        .window("Flux", surface.size.width, surface.size.height)
+    let mut window_builder =
+        video_subsystem.window("Flux", surface.size.width, surface.size.height);
+    window_builder
         .position(surface.position.x, surface.position.y)
-        .input_grabbed()
         .borderless()
         .hidden()
-        .allow_highdpi()
-        .build()
-        .map_err(|err| err.to_string())?;
+        .allow_highdpi();
+
+    if grab_input {
+        window_builder.input_grabbed();
+    }
+
+    let window = window_builder.build().map_err(|err| err.to_string())?;
 
     #[cfg(windows)]
     unsafe {
         platform::windows::window::enable_transparency(&window.raw_window_handle())
     };
 
-    let gl_context = gl_context::new_gl_context(
-        window.raw_display_handle(),
-        window.size().into(),
-        window.raw_window_handle(),
-        None,
-    );
+    let surface = surface::Surface {
+        wallpaper: surface_wallpaper(video_subsystem, config, surface),
+        ..surface.clone()
+    };
 
-    let swapchain = create_swapchain(&window.raw_window_handle(), &gl_context);
-
-    let physical_size = surface.size;
-    let logical_size = physical_size.to_logical(surface.scale_factor);
-    let settings = config.to_settings(surface.wallpaper.clone());
-    let flux = Flux::new(
-        &Rc::clone(&gl_context.gl),
-        logical_size.width,
-        logical_size.height,
-        physical_size.width,
-        physical_size.height,
-        &Rc::new(settings),
-    )
-    .map_err(|err| err.to_string())?;
+    let target = ContextTarget {
+        raw_window_handle: window.raw_window_handle(),
+        attr_window: None,
+        dxgi: true,
+    };
 
-    Ok(Instance {
-        flux,
-        gl_context,
-        window,
-        swapchain,
-    })
+    Instance::new(window, target, surface, config, false)
+}
+
+// A new window in the same place, and with the same flags, to try a different pixel format in.
+#[cfg(windows)]
+fn recreate_window(window: &Window) -> Result<Window, String> {
+    let (x, y) = window.position();
+    let (width, height) = window.size();
+
+    window
+        .subsystem()
+        .window(window.title(), width, height)
+        .position(x, y)
+        .set_window_flags(window.window_flags())
+        .build()
+        .map_err(|err| err.to_string())
 }
 
 #[cfg(not(windows))]
 fn create_swapchain(
-    raw_window_handle: &RawWindowHandle,
+    _raw_window_handle: &RawWindowHandle,
     gl_context: &gl_context::GLContext,
 ) -> Swapchain {
-    Swapchain::Gl
+    Swapchain::Gl {
+        vsync: enable_vsync(gl_context),
+    }
 }
 
 #[cfg(windows)]
@@ -445,23 +1533,30 @@ fn create_swapchain(
     match dxgi_interop {
         Ok(dxgi_interop) => Swapchain::Dxgi(dxgi_interop),
         Err(err) => {
-            use glutin::surface::SwapInterval;
-            use std::num::NonZeroU32;
-
             log::warn!(
                 "Failed to create DXGI swapchain: {}. Falling back to GL.",
                 err
             );
 
-            // Try setting vsync.
-            if let Err(res) = gl_context.surface.set_swap_interval(
-                &gl_context.context,
-                SwapInterval::Wait(NonZeroU32::new(1).unwrap()),
-            ) {
-                log::error!("Failed to set vsync: {res:?}");
+            Swapchain::Gl {
+                vsync: enable_vsync(gl_context),
             }
+        }
+    }
+}
 
-            Swapchain::Gl
+fn enable_vsync(gl_context: &gl_context::GLContext) -> bool {
+    use glutin::surface::SwapInterval;
+    use std::num::NonZeroU32;
+
+    match gl_context.surface.set_swap_interval(
+        &gl_context.context,
+        SwapInterval::Wait(NonZeroU32::new(1).unwrap()),
+    ) {
+        Ok(()) => true,
+        Err(res) => {
+            log::error!("Failed to set vsync: {res:?}");
+            false
         }
     }
 }
@@ -1,2 +1,53 @@
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
 #[cfg(windows)]
 pub mod windows;
+
+#[cfg(unix)]
+pub mod unix;
+
+/// The local time of day, in minutes since midnight.
+pub fn local_minutes_since_midnight() -> Option<u32> {
+    #[cfg(windows)]
+    {
+        windows::local_time::minutes_since_midnight()
+    }
+
+    #[cfg(unix)]
+    {
+        unix::local_time::minutes_since_midnight()
+    }
+
+    #[cfg(not(any(windows, unix)))]
+    {
+        None
+    }
+}
+
+/// The contents of an area of the screen, as tightly packed RGBA rows from top to bottom.
+pub struct Screenshot {
+    pub size: PhysicalSize<u32>,
+    pub pixels: Vec<u8>,
+}
+
+/// Capture an area of the desktop, in physical pixels.
+pub fn capture_screen(
+    position: PhysicalPosition<i32>,
+    size: PhysicalSize<u32>,
+) -> Result<Screenshot, String> {
+    #[cfg(windows)]
+    {
+        windows::screen_capture::capture(position, size)
+    }
+
+    #[cfg(unix)]
+    {
+        unix::screen_capture::capture(position, size)
+    }
+
+    #[cfg(not(any(windows, unix)))]
+    {
+        let _ = (position, size);
+        Err("Capturing the screen isn’t supported on this platform".to_string())
+    }
+}
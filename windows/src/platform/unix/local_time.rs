@@ -0,0 +1,13 @@
+/// The local time of day, in minutes since midnight.
+pub fn minutes_since_midnight() -> Option<u32> {
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm = std::mem::zeroed::<libc::tm>();
+
+        if libc::localtime_r(&now, &mut tm).is_null() {
+            return None;
+        }
+
+        Some(tm.tm_hour as u32 * 60 + tm.tm_min as u32)
+    }
+}
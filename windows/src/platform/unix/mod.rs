@@ -0,0 +1,3 @@
+pub mod local_time;
+pub mod screen_capture;
+pub mod xlib_error_hook;
@@ -0,0 +1,170 @@
+use crate::platform::Screenshot;
+
+use std::os::raw::c_ulong;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use x11_dl::xlib;
+
+/// Capture an area of the X11 root window, which spans every monitor.
+pub fn capture(
+    position: PhysicalPosition<i32>,
+    size: PhysicalSize<u32>,
+) -> Result<Screenshot, String> {
+    let xlib = xlib::Xlib::open().map_err(|err| format!("Failed to load Xlib: {}", err))?;
+
+    unsafe {
+        let display = (xlib.XOpenDisplay)(std::ptr::null());
+        if display.is_null() {
+            return Err("Can’t open the X11 display".to_string());
+        }
+
+        let screenshot = capture_root_window(&xlib, display, position, size);
+        (xlib.XCloseDisplay)(display);
+
+        screenshot
+    }
+}
+
+unsafe fn capture_root_window(
+    xlib: &xlib::Xlib,
+    display: *mut xlib::Display,
+    position: PhysicalPosition<i32>,
+    size: PhysicalSize<u32>,
+) -> Result<Screenshot, String> {
+    let root = (xlib.XDefaultRootWindow)(display);
+    let image = (xlib.XGetImage)(
+        display,
+        root,
+        position.x,
+        position.y,
+        size.width,
+        size.height,
+        (xlib.XAllPlanes)(),
+        xlib::ZPixmap,
+    );
+
+    if image.is_null() {
+        return Err("Failed to read the contents of the root window".to_string());
+    }
+
+    let pixels = read_pixels(&*image);
+    (xlib.XDestroyImage)(image);
+
+    Ok(Screenshot {
+        size,
+        pixels: pixels?,
+    })
+}
+
+// Convert the image to tightly packed RGBA, using the channel masks of the visual.
+unsafe fn read_pixels(image: &xlib::XImage) -> Result<Vec<u8>, String> {
+    if image.bits_per_pixel != 32 {
+        return Err(format!(
+            "Unsupported pixel format: {} bits per pixel",
+            image.bits_per_pixel
+        ));
+    }
+
+    let width = image.width as usize;
+    let height = image.height as usize;
+    let mut pixels = Vec::with_capacity(width * height * 4);
+
+    for y in 0..height {
+        let row = std::slice::from_raw_parts(
+            image.data.add(y * image.bytes_per_line as usize) as *const u8,
+            width * 4,
+        );
+
+        for pixel in row.chunks_exact(4) {
+            let bytes = [pixel[0], pixel[1], pixel[2], pixel[3]];
+            let value = if image.byte_order == xlib::LSBFirst {
+                u32::from_le_bytes(bytes)
+            } else {
+                u32::from_be_bytes(bytes)
+            };
+
+            pixels.extend_from_slice(&[
+                channel(value, image.red_mask),
+                channel(value, image.green_mask),
+                channel(value, image.blue_mask),
+                255,
+            ]);
+        }
+    }
+
+    Ok(pixels)
+}
+
+// Extract a channel and scale it to 8 bits.
+fn channel(value: u32, mask: c_ulong) -> u8 {
+    let mask = mask as u32;
+    if mask == 0 {
+        return 0;
+    }
+
+    let shift = mask.trailing_zeros();
+    let bits = (mask >> shift).count_ones();
+    let channel = (value & mask) >> shift;
+
+    if bits >= 8 {
+        (channel >> (bits - 8)) as u8
+    } else {
+        (channel * 255 / ((1 << bits) - 1)) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_scales_channels_to_8_bits() {
+        // 8 bits per channel
+        assert_eq!(channel(0x00ff8000, 0x00ff0000), 0xff);
+        assert_eq!(channel(0x00ff8000, 0x0000ff00), 0x80);
+        assert_eq!(channel(0x00ff8000, 0x000000ff), 0x00);
+
+        // RGB565
+        assert_eq!(channel(0xf800, 0xf800), 0xff);
+        assert_eq!(channel(0x07e0, 0x07e0), 0xff);
+        assert_eq!(channel(0x0010, 0x001f), 131);
+
+        // 10 bits per channel
+        assert_eq!(channel(0x3ff00000, 0x3ff00000), 0xff);
+
+        assert_eq!(channel(0xffffffff, 0), 0);
+    }
+
+    // Needs an X server. Run with `xvfb-run cargo test -- --include-ignored`.
+    #[test]
+    #[ignore]
+    fn it_captures_the_root_window() {
+        let xlib = xlib::Xlib::open().unwrap();
+
+        unsafe {
+            let display = (xlib.XOpenDisplay)(std::ptr::null());
+            assert!(!display.is_null(), "Can’t open the X11 display");
+
+            // Paint the root window pure red.
+            let screen = (xlib.XDefaultScreen)(display);
+            let root = (xlib.XRootWindow)(display, screen);
+            let visual = (xlib.XDefaultVisual)(display, screen);
+            (xlib.XSetWindowBackground)(display, root, (*visual).red_mask);
+            (xlib.XClearWindow)(display, root);
+            (xlib.XSync)(display, xlib::False);
+
+            let screenshot = capture(PhysicalPosition::new(10, 20), PhysicalSize::new(64, 48));
+
+            // Keep our connection open until after the capture. Xvfb resets the screen when the
+            // last client disconnects.
+            (xlib.XCloseDisplay)(display);
+
+            let screenshot = screenshot.unwrap();
+            assert_eq!(screenshot.size, PhysicalSize::new(64, 48));
+            assert_eq!(screenshot.pixels.len(), 64 * 48 * 4);
+            assert!(screenshot
+                .pixels
+                .chunks_exact(4)
+                .all(|pixel| pixel == [255, 0, 0, 255]));
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use std::ffi::c_void;
+use std::os::raw::c_int;
+use std::sync::Mutex;
+
+use glutin::api::glx::XlibErrorHookRegistrar;
+use x11_dl::xlib;
+
+type Hook = Box<dyn Fn(*mut c_void, *mut c_void) -> bool + Send + Sync>;
+type ErrorHandler = unsafe extern "C" fn(*mut xlib::Display, *mut xlib::XErrorEvent) -> c_int;
+
+static HOOKS: Mutex<Vec<Hook>> = Mutex::new(Vec::new());
+
+// The handler we replaced, usually SDL’s. We pass on the errors the hooks don’t handle.
+static PREVIOUS_HANDLER: Mutex<Option<ErrorHandler>> = Mutex::new(None);
+
+/// Let GLX catch the X errors it causes while creating a context.
+///
+/// By default, Xlib exits the process on any error. GLX expects to be able to probe for features
+/// and recover from the errors, so it needs to see them first. Winit usually sets this up, but we
+/// create our windows with SDL.
+pub fn registrar() -> XlibErrorHookRegistrar {
+    Box::new(|hook| {
+        let mut hooks = HOOKS.lock().unwrap();
+
+        if hooks.is_empty() {
+            if let Err(err) = install_handler() {
+                log::warn!("Failed to install the Xlib error handler: {}", err);
+            }
+        }
+
+        hooks.push(hook);
+    })
+}
+
+fn install_handler() -> Result<(), String> {
+    let xlib = xlib::Xlib::open().map_err(|err| format!("Failed to load Xlib: {}", err))?;
+
+    let previous = unsafe { (xlib.XSetErrorHandler)(Some(handle_error)) };
+    *PREVIOUS_HANDLER.lock().unwrap() = previous;
+
+    // SDL has already loaded Xlib, and our handler has to outlive any use of it.
+    std::mem::forget(xlib);
+
+    Ok(())
+}
+
+unsafe extern "C" fn handle_error(
+    display: *mut xlib::Display,
+    event: *mut xlib::XErrorEvent,
+) -> c_int {
+    let handled = HOOKS
+        .lock()
+        .map(|hooks| {
+            hooks
+                .iter()
+                .any(|hook| hook(display as *mut c_void, event as *mut c_void))
+        })
+        .unwrap_or(false);
+
+    if handled {
+        return 0;
+    }
+
+    match *PREVIOUS_HANDLER.lock().unwrap() {
+        Some(previous) => previous(display, event),
+        None => {
+            log::error!("Unhandled X error: code {}", (*event).error_code);
+            0
+        }
+    }
+}
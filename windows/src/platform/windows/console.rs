@@ -0,0 +1,9 @@
+use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+
+// Release builds use the "windows" subsystem, so there’s no console to print to. Borrow the
+// console of the terminal we were launched from, if there is one.
+pub fn attach_parent_console() {
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
@@ -19,7 +19,8 @@ use windows::Win32::Graphics::Dxgi::Common::{
     DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_MODE_DESC, DXGI_SAMPLE_DESC,
 };
 use windows::Win32::Graphics::Dxgi::{
-    IDXGISwapChain, DXGI_SWAP_CHAIN_DESC, DXGI_SWAP_EFFECT_DISCARD, DXGI_USAGE_RENDER_TARGET_OUTPUT,
+    IDXGISwapChain, DXGI_ERROR_DEVICE_REMOVED, DXGI_ERROR_DEVICE_RESET, DXGI_SWAP_CHAIN_DESC,
+    DXGI_SWAP_EFFECT_DISCARD, DXGI_USAGE_RENDER_TARGET_OUTPUT,
 };
 use windows::Win32::Graphics::Gdi::HDC;
 use windows::Win32::Graphics::OpenGL::{wglGetCurrentDC, wglGetProcAddress};
@@ -59,6 +60,8 @@ pub(crate) struct DXGIInterop {
     dx_interop: WGLDXInteropExtensionFunctions,
     color_handle_gl: HANDLE,
     fbo: GL::NativeFramebuffer,
+    // Wait for the vertical blank before presenting. The benchmark turns this off.
+    pub(crate) vsync: bool,
 }
 
 type GLint = c_int;
@@ -91,7 +94,7 @@ pub(crate) struct WGLDXInteropExtensionFunctions {
 pub(crate) unsafe fn with_dxgi_swapchain(
     dxgi_interop: &mut DXGIInterop,
     render: impl FnOnce(&GL::NativeFramebuffer),
-) {
+) -> windows::core::Result<()> {
     (dxgi_interop.dx_interop.DXLockObjectsNV)(
         dxgi_interop.gl_handle_d3d,
         1,
@@ -106,7 +109,15 @@ pub(crate) unsafe fn with_dxgi_swapchain(
         &mut dxgi_interop.color_handle_gl as *mut _,
     );
 
-    let _ = dxgi_interop.swap_chain.Present(1, 0);
+    let sync_interval = u32::from(dxgi_interop.vsync);
+    dxgi_interop.swap_chain.Present(sync_interval, 0).ok()
+}
+
+// Whether presenting failed because the GPU went away, like after a driver reset or update. The
+// device and everything on it has to be recreated.
+pub(crate) fn is_device_lost(err: &windows::core::Error) -> bool {
+    let code = err.code();
+    code == DXGI_ERROR_DEVICE_REMOVED || code == DXGI_ERROR_DEVICE_RESET
 }
 
 // Detect Intel GPUs.
@@ -346,6 +357,7 @@ pub(crate) fn create_dxgi_swapchain(
             dx_interop,
             color_handle_gl,
             fbo,
+            vsync: true,
         })
     }
 }
@@ -0,0 +1,8 @@
+use windows::Win32::System::SystemInformation::GetLocalTime;
+
+/// The local time of day, in minutes since midnight.
+pub fn minutes_since_midnight() -> Option<u32> {
+    let time = unsafe { GetLocalTime() };
+
+    Some(time.wHour as u32 * 60 + time.wMinute as u32)
+}
@@ -1,3 +1,8 @@
+pub mod console;
 pub mod dpi_awareness;
 pub mod dxgi_swapchain;
+pub mod local_time;
+pub mod monitor;
+pub mod screen_capture;
+pub mod timer;
 pub mod window;
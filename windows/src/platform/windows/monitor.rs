@@ -0,0 +1,69 @@
+use crate::surface::Rotation;
+
+use windows::core::HSTRING;
+use windows::Win32::Graphics::Gdi::{
+    EnumDisplayDevicesW, EnumDisplaySettingsW, DEVMODEW, DISPLAY_DEVICEW, DMDO_180, DMDO_270,
+    DMDO_90, DMDO_DEFAULT, ENUM_CURRENT_SETTINGS,
+};
+use windows::Win32::UI::WindowsAndMessaging::EDD_GET_DEVICE_INTERFACE_NAME;
+
+// Device names, like `\\.\DISPLAY1`, are handed out in enumeration order and can change between
+// reboots. The device interface name of the attached monitor includes the model and the port it’s
+// connected to, so it stays the same for as long as the monitor is plugged into the same port.
+pub fn device_interface_name(device_name: &str) -> Option<String> {
+    let mut device = DISPLAY_DEVICEW {
+        cb: std::mem::size_of::<DISPLAY_DEVICEW>() as u32,
+        ..Default::default()
+    };
+
+    let found = unsafe {
+        EnumDisplayDevicesW(
+            &HSTRING::from(device_name),
+            0,
+            &mut device,
+            EDD_GET_DEVICE_INTERFACE_NAME,
+        )
+    };
+
+    if !found.as_bool() {
+        return None;
+    }
+
+    let len = device
+        .DeviceID
+        .iter()
+        .position(|&c| c == 0)
+        .unwrap_or(device.DeviceID.len());
+
+    match String::from_utf16_lossy(&device.DeviceID[..len]) {
+        id if id.is_empty() => None,
+        id => Some(id),
+    }
+}
+
+pub fn rotation(device_name: &str) -> Option<Rotation> {
+    let mut mode = DEVMODEW {
+        dmSize: std::mem::size_of::<DEVMODEW>() as u16,
+        ..Default::default()
+    };
+
+    let found = unsafe {
+        EnumDisplaySettingsW(
+            &HSTRING::from(device_name),
+            ENUM_CURRENT_SETTINGS,
+            &mut mode,
+        )
+    };
+
+    if !found.as_bool() {
+        return None;
+    }
+
+    match unsafe { mode.Anonymous1.Anonymous2.dmDisplayOrientation } {
+        DMDO_DEFAULT => Some(Rotation::Normal),
+        DMDO_90 => Some(Rotation::Rotate90),
+        DMDO_180 => Some(Rotation::Rotate180),
+        DMDO_270 => Some(Rotation::Rotate270),
+        _ => None,
+    }
+}
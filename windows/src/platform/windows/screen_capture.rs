@@ -0,0 +1,89 @@
+use crate::platform::Screenshot;
+
+use windows::Win32::Foundation::HWND;
+use windows::Win32::Graphics::Gdi::{
+    BitBlt, CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject, GetDC, GetDIBits,
+    ReleaseDC, SelectObject, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, CAPTUREBLT, DIB_RGB_COLORS,
+    SRCCOPY,
+};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+/// Capture an area of the virtual screen, which spans every monitor.
+pub fn capture(
+    position: PhysicalPosition<i32>,
+    size: PhysicalSize<u32>,
+) -> Result<Screenshot, String> {
+    let width = size.width as i32;
+    let height = size.height as i32;
+
+    unsafe {
+        let screen_dc = GetDC(HWND::default());
+        if screen_dc.is_invalid() {
+            return Err("Failed to get the screen device context".to_string());
+        }
+
+        let memory_dc = CreateCompatibleDC(screen_dc);
+        let bitmap = CreateCompatibleBitmap(screen_dc, width, height);
+        let previous = SelectObject(memory_dc, bitmap);
+
+        // CAPTUREBLT includes layered windows, like the taskbar.
+        let copied = BitBlt(
+            memory_dc,
+            0,
+            0,
+            width,
+            height,
+            screen_dc,
+            position.x,
+            position.y,
+            SRCCOPY | CAPTUREBLT,
+        )
+        .as_bool();
+
+        SelectObject(memory_dc, previous);
+
+        let mut info = BITMAPINFO {
+            bmiHeader: BITMAPINFOHEADER {
+                biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+                biWidth: width,
+                // A negative height asks for rows from top to bottom.
+                biHeight: -height,
+                biPlanes: 1,
+                biBitCount: 32,
+                biCompression: BI_RGB.0 as u32,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut pixels = vec![0u8; size.width as usize * size.height as usize * 4];
+        let lines = if copied {
+            GetDIBits(
+                memory_dc,
+                bitmap,
+                0,
+                size.height,
+                Some(pixels.as_mut_ptr() as *mut _),
+                &mut info,
+                DIB_RGB_COLORS,
+            )
+        } else {
+            0
+        };
+
+        DeleteObject(bitmap);
+        DeleteDC(memory_dc);
+        ReleaseDC(HWND::default(), screen_dc);
+
+        if lines == 0 {
+            return Err("Failed to copy the screen contents".to_string());
+        }
+
+        // GDI hands us BGRX.
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+            pixel[3] = 255;
+        }
+
+        Ok(Screenshot { size, pixels })
+    }
+}
@@ -0,0 +1,27 @@
+use windows::Win32::Media::{timeBeginPeriod, timeEndPeriod, TIMERR_NOERROR};
+
+/// Raise the resolution of the system timer for as long as this is alive.
+///
+/// The default resolution of about 15.6ms is too coarse to pace frames with `thread::sleep`.
+pub struct TimerResolution {
+    period_ms: u32,
+}
+
+impl TimerResolution {
+    pub fn new(period_ms: u32) -> Option<Self> {
+        if unsafe { timeBeginPeriod(period_ms) } != TIMERR_NOERROR {
+            log::debug!("Failed to set the timer resolution to {period_ms}ms");
+            return None;
+        }
+
+        Some(Self { period_ms })
+    }
+}
+
+impl Drop for TimerResolution {
+    fn drop(&mut self) {
+        unsafe {
+            timeEndPeriod(self.period_ms);
+        }
+    }
+}
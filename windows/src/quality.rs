@@ -0,0 +1,263 @@
+use std::time::Duration;
+
+/// A step on the quality ladder, applied on top of the configured settings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quality {
+    /// Scales the resolution of the fluid simulation.
+    pub fluid_scale: f32,
+    /// Scales the spacing between lines. Larger values draw fewer lines.
+    pub line_spacing_scale: f32,
+    /// Scales the resolution we render at.
+    pub render_scale: f32,
+}
+
+// From best to worst.
+const LADDER: [Quality; 4] = [
+    Quality {
+        fluid_scale: 1.0,
+        line_spacing_scale: 1.0,
+        render_scale: 1.0,
+    },
+    Quality {
+        fluid_scale: 0.75,
+        line_spacing_scale: 1.25,
+        render_scale: 1.0,
+    },
+    Quality {
+        fluid_scale: 0.5,
+        line_spacing_scale: 1.5,
+        render_scale: 0.75,
+    },
+    Quality {
+        fluid_scale: 0.5,
+        line_spacing_scale: 2.0,
+        render_scale: 0.5,
+    },
+];
+
+// The fluid becomes too blocky to look like a fluid below this size.
+const MIN_FLUID_SIZE: u32 = 32;
+
+impl Quality {
+    pub fn apply(&self, settings: &mut flux::settings::Settings) {
+        settings.fluid_size = ((settings.fluid_size as f32 * self.fluid_scale).round() as u32)
+            .max(MIN_FLUID_SIZE.min(settings.fluid_size));
+        settings.grid_spacing =
+            ((settings.grid_spacing as f32 * self.line_spacing_scale).round() as u32).max(1);
+    }
+}
+
+// Lower the quality once the average frame time exceeds the budget by this much.
+const DOWNGRADE_THRESHOLD: f64 = 1.1;
+
+// Only consider raising the quality while the average frame time stays below this fraction of
+// the budget. The gap between the two thresholds keeps us from bouncing between two levels.
+const UPGRADE_THRESHOLD: f64 = 0.6;
+
+// Ignore the first frames after a change. Resizing the simulation causes a few slow frames.
+const SETTLE_FRAMES: u32 = 60;
+
+// How many frames of headroom we need before trying a higher quality level.
+const UPGRADE_FRAMES: u32 = 600;
+
+// Each failed attempt to raise the quality doubles the wait before the next one, up to this many
+// times.
+const MAX_UPGRADE_BACKOFF: u32 = 4;
+
+// Weight of the latest frame in the moving average of frame times.
+const SMOOTHING: f64 = 0.05;
+
+/// Adjusts the quality of an instance to fit its frame time into a budget.
+pub struct QualityController {
+    budget: Duration,
+    level: usize,
+    average_frame_time: Option<f64>,
+    frames_at_level: u32,
+    headroom_frames: u32,
+    last_change_was_upgrade: bool,
+    failed_upgrades: u32,
+}
+
+impl QualityController {
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            budget,
+            level: 0,
+            average_frame_time: None,
+            frames_at_level: 0,
+            headroom_frames: 0,
+            last_change_was_upgrade: false,
+            failed_upgrades: 0,
+        }
+    }
+
+    pub fn quality(&self) -> Quality {
+        LADDER[self.level]
+    }
+
+    /// Record the time it took to render a frame. Returns the new quality if it should change.
+    pub fn record(&mut self, frame_time: Duration) -> Option<Quality> {
+        let frame_time = frame_time.as_secs_f64();
+        let average_frame_time = match self.average_frame_time {
+            None => frame_time,
+            Some(average) => average + SMOOTHING * (frame_time - average),
+        };
+        self.average_frame_time = Some(average_frame_time);
+        self.frames_at_level = self.frames_at_level.saturating_add(1);
+
+        if self.frames_at_level < SETTLE_FRAMES {
+            return None;
+        }
+
+        let budget = self.budget.as_secs_f64();
+
+        if average_frame_time > budget * DOWNGRADE_THRESHOLD && self.level + 1 < LADDER.len() {
+            // We just raised the quality and couldn’t keep up. Wait longer before trying again.
+            if self.last_change_was_upgrade && self.frames_at_level < UPGRADE_FRAMES {
+                self.failed_upgrades = (self.failed_upgrades + 1).min(MAX_UPGRADE_BACKOFF);
+            }
+
+            return Some(self.change_level(self.level + 1));
+        }
+
+        if average_frame_time < budget * UPGRADE_THRESHOLD && self.level > 0 {
+            self.headroom_frames += 1;
+
+            if self.headroom_frames >= UPGRADE_FRAMES << self.failed_upgrades {
+                return Some(self.change_level(self.level - 1));
+            }
+        } else {
+            self.headroom_frames = 0;
+        }
+
+        None
+    }
+
+    fn change_level(&mut self, level: usize) -> Quality {
+        self.last_change_was_upgrade = level < self.level;
+        self.level = level;
+        self.average_frame_time = None;
+        self.frames_at_level = 0;
+        self.headroom_frames = 0;
+
+        LADDER[level]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUDGET: Duration = Duration::from_millis(10);
+    const SLOW: Duration = Duration::from_millis(15);
+    const FAST: Duration = Duration::from_millis(3);
+
+    // Feed the same frame time until the quality changes. Returns how many frames that took.
+    fn frames_until_change(
+        controller: &mut QualityController,
+        frame_time: Duration,
+        limit: u32,
+    ) -> Option<(u32, Quality)> {
+        (1..=limit).find_map(|frame| {
+            controller
+                .record(frame_time)
+                .map(|quality| (frame, quality))
+        })
+    }
+
+    #[test]
+    fn it_lowers_the_quality_when_frames_are_slow() {
+        let mut controller = QualityController::new(BUDGET);
+
+        assert_eq!(
+            frames_until_change(&mut controller, SLOW, 10_000),
+            Some((SETTLE_FRAMES, LADDER[1]))
+        );
+        assert_eq!(controller.quality(), LADDER[1]);
+    }
+
+    #[test]
+    fn it_stops_at_the_lowest_quality() {
+        let mut controller = QualityController::new(BUDGET);
+
+        for _ in 0..100_000 {
+            controller.record(SLOW);
+        }
+
+        assert_eq!(controller.quality(), LADDER[LADDER.len() - 1]);
+    }
+
+    #[test]
+    fn it_does_not_raise_the_quality_at_the_highest_level() {
+        let mut controller = QualityController::new(BUDGET);
+
+        assert_eq!(frames_until_change(&mut controller, FAST, 10_000), None);
+    }
+
+    #[test]
+    fn it_raises_the_quality_after_sustained_headroom() {
+        let mut controller = QualityController::new(BUDGET);
+        frames_until_change(&mut controller, SLOW, 10_000);
+
+        assert_eq!(
+            frames_until_change(&mut controller, FAST, 10_000),
+            Some((SETTLE_FRAMES + UPGRADE_FRAMES - 1, LADDER[0]))
+        );
+    }
+
+    #[test]
+    fn it_holds_the_level_between_the_thresholds() {
+        let mut controller = QualityController::new(BUDGET);
+        frames_until_change(&mut controller, SLOW, 10_000);
+
+        // Too slow to raise the quality, but fast enough to keep the current level.
+        let steady = Duration::from_millis(8);
+        assert_eq!(frames_until_change(&mut controller, steady, 100_000), None);
+        assert_eq!(controller.quality(), LADDER[1]);
+    }
+
+    #[test]
+    fn it_does_not_oscillate_on_noisy_frame_times() {
+        let mut controller = QualityController::new(BUDGET);
+        frames_until_change(&mut controller, SLOW, 10_000);
+
+        // Every other frame is well over budget, but the average stays between the thresholds.
+        let changes = (0..100_000)
+            .filter_map(|frame| {
+                let frame_time = if frame % 2 == 0 { 5 } else { 11 };
+                controller.record(Duration::from_millis(frame_time))
+            })
+            .count();
+
+        assert_eq!(changes, 0);
+    }
+
+    #[test]
+    fn it_tolerates_a_single_slow_frame() {
+        let mut controller = QualityController::new(BUDGET);
+
+        for frame in 0..10_000 {
+            let frame_time = if frame == 5_000 { 100 } else { 5 };
+            assert_eq!(controller.record(Duration::from_millis(frame_time)), None);
+        }
+    }
+
+    #[test]
+    fn it_backs_off_after_a_failed_upgrade() {
+        let mut controller = QualityController::new(BUDGET);
+        frames_until_change(&mut controller, SLOW, 10_000);
+        frames_until_change(&mut controller, FAST, 10_000);
+
+        // The higher quality is too slow again, so we drop back down.
+        assert_eq!(
+            frames_until_change(&mut controller, SLOW, 10_000),
+            Some((SETTLE_FRAMES, LADDER[1]))
+        );
+
+        // And wait twice as long before the next attempt.
+        assert_eq!(
+            frames_until_change(&mut controller, FAST, 10_000),
+            Some((SETTLE_FRAMES + 2 * UPGRADE_FRAMES - 1, LADDER[0]))
+        );
+    }
+}
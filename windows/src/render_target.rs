@@ -0,0 +1,124 @@
+use glow as GL;
+use glow::HasContext;
+use winit::dpi::PhysicalSize;
+
+/// An offscreen framebuffer to render into at a lower resolution than the window, or without a
+/// window at all.
+pub struct RenderTarget {
+    framebuffer: GL::NativeFramebuffer,
+    renderbuffer: GL::NativeRenderbuffer,
+    size: PhysicalSize<u32>,
+}
+
+impl RenderTarget {
+    pub fn new(gl: &glow::Context, size: PhysicalSize<u32>) -> Result<Self, String> {
+        unsafe {
+            let framebuffer = gl.create_framebuffer()?;
+            let renderbuffer = gl.create_renderbuffer()?;
+
+            gl.bind_renderbuffer(GL::RENDERBUFFER, Some(renderbuffer));
+            gl.renderbuffer_storage(
+                GL::RENDERBUFFER,
+                GL::RGBA8,
+                size.width as i32,
+                size.height as i32,
+            );
+            gl.bind_renderbuffer(GL::RENDERBUFFER, None);
+
+            gl.bind_framebuffer(GL::FRAMEBUFFER, Some(framebuffer));
+            gl.framebuffer_renderbuffer(
+                GL::FRAMEBUFFER,
+                GL::COLOR_ATTACHMENT0,
+                GL::RENDERBUFFER,
+                Some(renderbuffer),
+            );
+            let status = gl.check_framebuffer_status(GL::FRAMEBUFFER);
+            gl.bind_framebuffer(GL::FRAMEBUFFER, None);
+
+            let render_target = Self {
+                framebuffer,
+                renderbuffer,
+                size,
+            };
+
+            if status != GL::FRAMEBUFFER_COMPLETE {
+                render_target.delete(gl);
+                return Err(format!("Offscreen framebuffer incomplete: {:#x}", status));
+            }
+
+            log::debug!(
+                "Created offscreen render target: {}x{}",
+                size.width,
+                size.height
+            );
+
+            Ok(render_target)
+        }
+    }
+
+    pub fn framebuffer(&self) -> GL::NativeFramebuffer {
+        self.framebuffer
+    }
+
+    /// Upscale the contents onto `target`, or the default framebuffer if `None`.
+    pub fn blit(
+        &self,
+        gl: &glow::Context,
+        target: Option<GL::NativeFramebuffer>,
+        target_size: PhysicalSize<u32>,
+    ) {
+        unsafe {
+            gl.bind_framebuffer(GL::READ_FRAMEBUFFER, Some(self.framebuffer));
+            gl.bind_framebuffer(GL::DRAW_FRAMEBUFFER, target);
+            gl.blit_framebuffer(
+                0,
+                0,
+                self.size.width as i32,
+                self.size.height as i32,
+                0,
+                0,
+                target_size.width as i32,
+                target_size.height as i32,
+                GL::COLOR_BUFFER_BIT,
+                GL::LINEAR,
+            );
+            gl.bind_framebuffer(GL::FRAMEBUFFER, target);
+        }
+    }
+
+    /// Read back the contents as tightly packed RGBA rows, from top to bottom.
+    pub fn read_pixels(&self, gl: &glow::Context) -> Vec<u8> {
+        let row_length = self.size.width as usize * 4;
+        let mut pixels = vec![0; row_length * self.size.height as usize];
+
+        unsafe {
+            gl.bind_framebuffer(GL::READ_FRAMEBUFFER, Some(self.framebuffer));
+            gl.pixel_store_i32(GL::PACK_ALIGNMENT, 1);
+            gl.read_pixels(
+                0,
+                0,
+                self.size.width as i32,
+                self.size.height as i32,
+                GL::RGBA,
+                GL::UNSIGNED_BYTE,
+                GL::PixelPackData::Slice(&mut pixels),
+            );
+            gl.bind_framebuffer(GL::READ_FRAMEBUFFER, None);
+        }
+
+        // OpenGL reads from the bottom up.
+        pixels
+            .chunks_exact(row_length)
+            .rev()
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    pub fn delete(self, gl: &glow::Context) {
+        unsafe {
+            gl.delete_framebuffer(self.framebuffer);
+            gl.delete_renderbuffer(self.renderbuffer);
+        }
+    }
+}
@@ -1,26 +1,70 @@
+use crate::winit_compat::{HasRotation, MonitorHandle};
+
 use std::path;
 
 use winit::dpi::{PhysicalPosition, PhysicalSize};
-use winit::monitor::MonitorHandle;
+
+/// The clockwise rotation of a monitor from its native orientation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    Normal,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+/// The position and size of a surface, which tells surfaces apart.
+pub type Bounds = (PhysicalPosition<i32>, PhysicalSize<u32>);
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Surface {
     pub position: PhysicalPosition<i32>,
     pub size: PhysicalSize<u32>,
     pub scale_factor: f64,
+    pub rotation: Rotation,
+    // Only looked up when creating a window for the surface. See `main::surface_wallpaper`.
     pub wallpaper: Option<path::PathBuf>,
+    // Cover the surface with black instead of drawing the animation.
+    pub blackout: bool,
 }
 
 impl Surface {
-    fn from_monitor(monitor: &MonitorHandle, wallpaper: &Option<path::PathBuf>) -> Self {
+    fn from_monitor(monitor: &MonitorHandle, blackout: bool) -> Self {
         Self {
             position: monitor.position(),
             size: monitor.size(),
             scale_factor: monitor.scale_factor(),
-            wallpaper: wallpaper.clone(),
+            rotation: monitor.rotation(),
+            wallpaper: None,
+            blackout,
         }
     }
 
+    pub fn contains(&self, point: PhysicalPosition<i32>) -> bool {
+        (self.position.x..self.position.x + self.size.width as i32).contains(&point.x)
+            && (self.position.y..self.position.y + self.size.height as i32).contains(&point.y)
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        (self.position, self.size)
+    }
+
+    // The size is reported in the rotated orientation, so this also covers landscape monitors
+    // rotated into portrait. Monitors are only combined with monitors of the same size, so
+    // portrait and landscape monitors always end up on separate surfaces.
+    pub fn is_portrait(&self) -> bool {
+        self.size.height > self.size.width
+    }
+
+    /// The ratio of the longer side to the shorter side.
+    pub fn aspect_ratio(&self) -> f32 {
+        let width = self.size.width.max(1) as f32;
+        let height = self.size.height.max(1) as f32;
+
+        width.max(height) / width.min(height)
+    }
+
     fn merge(&mut self, surface: &Self) {
         // if self.scale_factor != surface.scale_factor {
         //     return None;
@@ -44,12 +88,103 @@ impl Surface {
             top_left.y.abs_diff(bottom_right.y),
         );
     }
+
+    fn has_same_bounds(&self, surface: &Self) -> bool {
+        self.position == surface.position
+            && self.size == surface.size
+            && self.scale_factor == surface.scale_factor
+            && self.blackout == surface.blackout
+    }
+
+    fn overlap_area(&self, surface: &Self) -> u64 {
+        let left = self.position.x.max(surface.position.x);
+        let top = self.position.y.max(surface.position.y);
+        let right = (self.position.x + self.size.width as i32)
+            .min(surface.position.x + surface.size.width as i32);
+        let bottom = (self.position.y + self.size.height as i32)
+            .min(surface.position.y + surface.size.height as i32);
+
+        if right <= left || bottom <= top {
+            return 0;
+        }
+
+        left.abs_diff(right) as u64 * top.abs_diff(bottom) as u64
+    }
 }
 
-pub fn combine_monitors(monitors: &[(MonitorHandle, Option<path::PathBuf>)]) -> Vec<Surface> {
+/// The changes needed to turn the surfaces we're currently drawing to into a newly planned set
+/// of surfaces, for example after a monitor is connected, removed, or changes resolution.
+#[derive(Debug, PartialEq)]
+pub struct SurfaceDiff<K> {
+    /// Existing surfaces that are still valid.
+    pub kept: Vec<K>,
+    /// Existing surfaces that should be moved and resized to a new surface.
+    pub resized: Vec<(K, Surface)>,
+    /// New surfaces that don't correspond to any existing surface.
+    pub created: Vec<Surface>,
+    /// Existing surfaces that no longer correspond to any monitor.
+    pub removed: Vec<K>,
+}
+
+impl<K> SurfaceDiff<K> {
+    pub fn is_empty(&self) -> bool {
+        self.resized.is_empty() && self.created.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Match the existing surfaces, identified by `K`, against the planned surfaces.
+///
+/// Surfaces with identical bounds are kept as-is. The remaining planned surfaces reuse the
+/// existing surface they overlap the most, so that a monitor changing resolution resizes its
+/// window instead of creating a new one. Switching between blacked out and animated always
+/// creates a new window, since the two are set up differently.
+pub fn diff_surfaces<K: Copy>(existing: &[(K, Surface)], planned: &[Surface]) -> SurfaceDiff<K> {
+    let mut unclaimed = existing.to_vec();
+    let mut unmatched = Vec::new();
+    let mut diff = SurfaceDiff {
+        kept: Vec::new(),
+        resized: Vec::new(),
+        created: Vec::new(),
+        removed: Vec::new(),
+    };
+
+    for surface in planned {
+        match unclaimed
+            .iter()
+            .position(|(_, existing_surface)| existing_surface.has_same_bounds(surface))
+        {
+            Some(index) => diff.kept.push(unclaimed.swap_remove(index).0),
+            None => unmatched.push(surface),
+        }
+    }
+
+    for surface in unmatched {
+        let best_match = unclaimed
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, existing_surface))| existing_surface.blackout == surface.blackout)
+            .map(|(index, (_, existing_surface))| (index, existing_surface.overlap_area(surface)))
+            .filter(|(_, area)| *area > 0)
+            .max_by_key(|(_, area)| *area);
+
+        match best_match {
+            Some((index, _)) => {
+                let (key, _) = unclaimed.swap_remove(index);
+                diff.resized.push((key, surface.clone()));
+            }
+            None => diff.created.push(surface.clone()),
+        }
+    }
+
+    diff.removed = unclaimed.into_iter().map(|(key, _)| key).collect();
+
+    diff
+}
+
+pub fn combine_monitors(monitors: &[(MonitorHandle, bool)]) -> Vec<Surface> {
     let surfaces = monitors
         .iter()
-        .map(|(handle, wallpaper)| Surface::from_monitor(handle, wallpaper))
+        .map(|(handle, blackout)| Surface::from_monitor(handle, *blackout))
         .collect();
 
     SurfaceGroup::new().add(surfaces).combine()
@@ -57,7 +192,8 @@ pub fn combine_monitors(monitors: &[(MonitorHandle, Option<path::PathBuf>)]) ->
 
 use std::collections::HashMap;
 struct SurfaceGroup {
-    grouping: HashMap<PhysicalSize<u32>, Surface>,
+    // Blacked out monitors are never combined with animated ones.
+    grouping: HashMap<(PhysicalSize<u32>, bool), Surface>,
     surfaces: Vec<Surface>,
 }
 
@@ -78,7 +214,7 @@ impl SurfaceGroup {
     fn combine(mut self) -> Vec<Surface> {
         for surface in self.surfaces.iter() {
             self.grouping
-                .entry(surface.size)
+                .entry((surface.size, surface.blackout))
                 .and_modify(|existing_surface| existing_surface.merge(surface))
                 .or_insert_with(|| surface.clone());
         }
@@ -87,107 +223,252 @@ impl SurfaceGroup {
     }
 }
 
-// #[cfg(test)]
-// mod test {
-//     use super::*;
-//
-//     #[test]
-//     fn it_does_not_combine_two_different_displays() {
-//         let display0 = Surface::from_bounds(Rect::new(0, 0, 3360, 2100), BASE_DPI as f64);
-//         let display1 = Surface::from_bounds(
-//             Rect::new(display0.bounds.width() as i32, 0, 2560, 1440),
-//             BASE_DPI as f64,
-//         );
-//
-//         assert_eq!(
-//             Surface::combine_displays(&[display0, display1]),
-//             vec![display0, display1]
-//         );
-//     }
-//
-//     #[test]
-//     fn it_partially_combines_two_1440p_displays_and_a_separate_laptop_display() {
-//         // 1440p + 1440p + laptop
-//         let display0 = Surface::from_bounds(Rect::new(-2560, 0, 2560, 1440), BASE_DPI as f64);
-//         let display1 = Surface::from_bounds(Rect::new(0, 0, 2560, 1440), BASE_DPI as f64);
-//         let display2 = Surface::from_bounds(Rect::new(2560, 0, 3360, 2100), BASE_DPI as f64);
-//
-//         assert_eq!(
-//             Surface::combine_displays(&[display0, display1, display2]),
-//             vec![
-//                 Surface::from_bounds(Rect::new(-2560, 0, 5120, 1440), BASE_DPI as f64),
-//                 display2
-//             ]
-//         );
-//
-//         // laptop + 1440p + 1440p
-//         let display2 = Surface::from_bounds(Rect::new(-1920, 360, 1920, 1080), BASE_DPI as f64);
-//         let display0 = Surface::from_bounds(Rect::new(0, 0, 2560, 1440), BASE_DPI as f64);
-//         let display1 = Surface::from_bounds(Rect::new(2560, 0, 2560, 1440), BASE_DPI as f64);
-//
-//         assert_eq!(
-//             Surface::combine_displays(&[display2, display0, display1]),
-//             vec![
-//                 display2,
-//                 Surface::from_bounds(Rect::new(0, 0, 5120, 1440), BASE_DPI as f64),
-//             ]
-//         );
-//     }
-//
-//     #[test]
-//     fn it_combines_two_1440p_displays() {
-//         let display0 = Surface::from_bounds(Rect::new(0, 0, 2560, 1440), BASE_DPI as f64);
-//         let display1 = Surface::from_bounds(
-//             Rect::new(display0.bounds.width() as i32, 0, 2560, 1440),
-//             BASE_DPI as f64,
-//         );
-//
-//         assert_eq!(
-//             Surface::combine_displays(&[display0, display1]),
-//             vec![Surface::from_bounds(
-//                 Rect::new(0, 0, 5120, 1440),
-//                 BASE_DPI as f64
-//             )]
-//         );
-//     }
-//
-//     #[test]
-//     fn it_combines_three_1440p_displays() {
-//         let display0 = Surface::from_bounds(Rect::new(-2560, 0, 2560, 1440), BASE_DPI as f64);
-//         let display1 = Surface::from_bounds(Rect::new(0, 0, 2560, 1440), BASE_DPI as f64);
-//         let display2 = Surface::from_bounds(Rect::new(2560, 0, 2560, 1440), BASE_DPI as f64);
-//
-//         assert_eq!(
-//             Surface::combine_displays(&[display0, display1, display2]),
-//             vec![Surface::from_bounds(
-//                 Rect::new(-2560, 0, 2560 * 3, 1440),
-//                 BASE_DPI as f64
-//             )]
-//         );
-//     }
-//
-//     #[test]
-//     fn it_combines_a_grid_of_displays() {
-//         let display0 = Surface::from_bounds(Rect::new(0, 0, 2560, 1440), BASE_DPI as f64);
-//         let display1 = Surface::from_bounds(Rect::new(2560, 0, 2560, 1440), BASE_DPI as f64);
-//         let display2 = Surface::from_bounds(Rect::new(0, 1440, 2560, 1440), BASE_DPI as f64);
-//         let display3 = Surface::from_bounds(Rect::new(2560, 1440, 2560, 1440), BASE_DPI as f64);
-//
-//         assert_eq!(
-//             Surface::combine_displays(&[display0, display1, display2, display3]),
-//             vec![Surface::from_bounds(
-//                 Rect::new(0, 0, 2560 * 2, 1440 * 2),
-//                 BASE_DPI as f64
-//             ),]
-//         );
-//
-//         let laptop = Surface::from_bounds(Rect::new(2560 * 2, 0, 1920, 1080), BASE_DPI as f64);
-//         assert_eq!(
-//             Surface::combine_displays(&[display0, display1, display2, display3, laptop]),
-//             vec![
-//                 Surface::from_bounds(Rect::new(0, 0, 2560 * 2, 1440 * 2), BASE_DPI as f64),
-//                 laptop
-//             ]
-//         );
-//     }
-// }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn surface(x: i32, y: i32, width: u32, height: u32) -> Surface {
+        Surface {
+            position: PhysicalPosition::new(x, y),
+            size: PhysicalSize::new(width, height),
+            scale_factor: 1.0,
+            rotation: Rotation::Normal,
+            wallpaper: None,
+            blackout: false,
+        }
+    }
+
+    #[test]
+    fn it_keeps_unchanged_surfaces() {
+        let existing = [
+            (0, surface(0, 0, 2560, 1440)),
+            (1, surface(2560, 0, 1920, 1080)),
+        ];
+        let planned = [surface(2560, 0, 1920, 1080), surface(0, 0, 2560, 1440)];
+
+        let diff = diff_surfaces(&existing, &planned);
+
+        assert!(diff.is_empty());
+        assert_eq!(diff.kept, vec![1, 0]);
+    }
+
+    #[test]
+    fn it_creates_surfaces_for_new_monitors() {
+        let existing = [(0, surface(0, 0, 2560, 1440))];
+        let planned = [surface(0, 0, 2560, 1440), surface(2560, 0, 1920, 1080)];
+
+        assert_eq!(
+            diff_surfaces(&existing, &planned),
+            SurfaceDiff {
+                kept: vec![0],
+                resized: vec![],
+                created: vec![surface(2560, 0, 1920, 1080)],
+                removed: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn it_removes_surfaces_for_disconnected_monitors() {
+        let existing = [
+            (0, surface(0, 0, 2560, 1440)),
+            (1, surface(2560, 0, 1920, 1080)),
+        ];
+        let planned = [surface(0, 0, 2560, 1440)];
+
+        assert_eq!(
+            diff_surfaces(&existing, &planned),
+            SurfaceDiff {
+                kept: vec![0],
+                resized: vec![],
+                created: vec![],
+                removed: vec![1],
+            }
+        );
+    }
+
+    #[test]
+    fn it_resizes_a_surface_when_the_resolution_changes() {
+        let existing = [
+            (0, surface(0, 0, 2560, 1440)),
+            (1, surface(2560, 0, 1920, 1080)),
+        ];
+        let planned = [surface(0, 0, 3840, 2160), surface(3840, 0, 1920, 1080)];
+
+        assert_eq!(
+            diff_surfaces(&existing, &planned),
+            SurfaceDiff {
+                kept: vec![],
+                resized: vec![
+                    (0, surface(0, 0, 3840, 2160)),
+                    (1, surface(3840, 0, 1920, 1080))
+                ],
+                created: vec![],
+                removed: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn it_resizes_the_surface_with_the_most_overlap() {
+        // Two monitors that used to be separate surfaces are now combined into one.
+        let existing = [
+            (0, surface(0, 0, 1920, 1080)),
+            (1, surface(1920, 0, 2560, 1440)),
+        ];
+        let planned = [surface(0, 0, 4480, 1440)];
+
+        assert_eq!(
+            diff_surfaces(&existing, &planned),
+            SurfaceDiff {
+                kept: vec![],
+                resized: vec![(1, surface(0, 0, 4480, 1440))],
+                created: vec![],
+                removed: vec![0],
+            }
+        );
+    }
+
+    #[test]
+    fn it_recreates_a_surface_when_blackout_changes() {
+        let mut blacked_out = surface(0, 0, 2560, 1440);
+        blacked_out.blackout = true;
+        let existing = [(0, surface(0, 0, 2560, 1440))];
+
+        let diff = diff_surfaces(&existing, &[blacked_out.clone()]);
+
+        assert!(diff.kept.is_empty());
+        assert!(diff.resized.is_empty());
+        assert_eq!(diff.created, vec![blacked_out]);
+        assert_eq!(diff.removed, vec![0]);
+    }
+
+    fn rotated(mut surface: Surface, rotation: Rotation) -> Surface {
+        surface.rotation = rotation;
+        surface
+    }
+
+    // The grouping doesn’t preserve the order of the monitors.
+    fn combine(surfaces: &[Surface]) -> Vec<Surface> {
+        let mut combined = SurfaceGroup::new().add(surfaces.to_vec()).combine();
+        combined.sort_by_key(|surface| (surface.position.x, surface.position.y));
+        combined
+    }
+
+    #[test]
+    fn it_does_not_combine_two_different_displays() {
+        let display0 = surface(0, 0, 3360, 2100);
+        let display1 = surface(3360, 0, 2560, 1440);
+
+        assert_eq!(
+            combine(&[display0.clone(), display1.clone()]),
+            vec![display0, display1]
+        );
+    }
+
+    #[test]
+    fn it_partially_combines_two_1440p_displays_and_a_separate_laptop_display() {
+        // 1440p + 1440p + laptop
+        let display0 = surface(-2560, 0, 2560, 1440);
+        let display1 = surface(0, 0, 2560, 1440);
+        let display2 = surface(2560, 0, 3360, 2100);
+
+        assert_eq!(
+            combine(&[display0, display1, display2.clone()]),
+            vec![surface(-2560, 0, 5120, 1440), display2]
+        );
+
+        // laptop + 1440p + 1440p
+        let display2 = surface(-1920, 360, 1920, 1080);
+        let display0 = surface(0, 0, 2560, 1440);
+        let display1 = surface(2560, 0, 2560, 1440);
+
+        assert_eq!(
+            combine(&[display2.clone(), display0, display1]),
+            vec![display2, surface(0, 0, 5120, 1440)]
+        );
+    }
+
+    #[test]
+    fn it_combines_two_1440p_displays() {
+        let display0 = surface(0, 0, 2560, 1440);
+        let display1 = surface(2560, 0, 2560, 1440);
+
+        assert_eq!(
+            combine(&[display0, display1]),
+            vec![surface(0, 0, 5120, 1440)]
+        );
+    }
+
+    #[test]
+    fn it_combines_three_1440p_displays() {
+        let display0 = surface(-2560, 0, 2560, 1440);
+        let display1 = surface(0, 0, 2560, 1440);
+        let display2 = surface(2560, 0, 2560, 1440);
+
+        assert_eq!(
+            combine(&[display0, display1, display2]),
+            vec![surface(-2560, 0, 2560 * 3, 1440)]
+        );
+    }
+
+    #[test]
+    fn it_combines_a_grid_of_displays() {
+        let display0 = surface(0, 0, 2560, 1440);
+        let display1 = surface(2560, 0, 2560, 1440);
+        let display2 = surface(0, 1440, 2560, 1440);
+        let display3 = surface(2560, 1440, 2560, 1440);
+        let grid = [display0, display1, display2, display3];
+
+        assert_eq!(combine(&grid), vec![surface(0, 0, 2560 * 2, 1440 * 2)]);
+
+        let laptop = surface(2560 * 2, 0, 1920, 1080);
+        let mut displays = grid.to_vec();
+        displays.push(laptop.clone());
+
+        assert_eq!(
+            combine(&displays),
+            vec![surface(0, 0, 2560 * 2, 1440 * 2), laptop]
+        );
+    }
+
+    #[test]
+    fn it_does_not_combine_a_portrait_display_with_a_landscape_display() {
+        let landscape = surface(0, 0, 2560, 1440);
+        let portrait = rotated(surface(2560, 0, 1440, 2560), Rotation::Rotate90);
+
+        let combined = combine(&[landscape.clone(), portrait.clone()]);
+
+        assert_eq!(combined, vec![landscape, portrait]);
+        assert!(!combined[0].is_portrait());
+        assert!(combined[1].is_portrait());
+    }
+
+    #[test]
+    fn it_combines_two_portrait_displays_of_the_same_size() {
+        let display0 = rotated(surface(0, 0, 1080, 1920), Rotation::Rotate90);
+        let display1 = rotated(surface(1080, 0, 1080, 1920), Rotation::Rotate90);
+
+        let combined = combine(&[display0, display1]);
+
+        assert_eq!(
+            combined,
+            vec![rotated(surface(0, 0, 2160, 1920), Rotation::Rotate90)]
+        );
+        // Side by side, the two portrait monitors make a landscape surface.
+        assert!(!combined[0].is_portrait());
+    }
+
+    #[test]
+    fn it_does_not_combine_the_same_panel_in_different_rotations() {
+        // The same 1080p panel, once in its native orientation, and once rotated into portrait.
+        let landscape = surface(0, 0, 1920, 1080);
+        let portrait = rotated(surface(1920, 0, 1080, 1920), Rotation::Rotate270);
+
+        let combined = combine(&[landscape.clone(), portrait.clone()]);
+
+        assert_eq!(combined, vec![landscape, portrait]);
+        assert_ne!(combined[0].is_portrait(), combined[1].is_portrait());
+        assert_eq!(combined[0].aspect_ratio(), combined[1].aspect_ratio());
+    }
+}
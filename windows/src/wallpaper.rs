@@ -13,7 +13,10 @@ pub fn get(monitor: &MonitorHandle) -> Result<PathBuf> {
 
         // TODO; check that the path is valid (file exists)
 
-        let path = wallpaper.to_string().unwrap();
+        // We own the string, and have to free it with the COM allocator.
+        let path = String::from_utf16_lossy(wallpaper.as_wide());
+        CoTaskMemFree(Some(wallpaper.as_ptr() as *const _));
+
         Ok(PathBuf::from(path))
     }
 }
@@ -1,16 +1,75 @@
+#[cfg(windows)]
 use std::collections::vec_deque;
+#[cfg(windows)]
 use std::iter::Map;
 use std::num::NonZeroU32;
 
 use sdl2::video::Window;
 use sdl2::VideoSubsystem;
 
+#[cfg(windows)]
 use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
 
+use crate::surface::Rotation;
+
+#[cfg(not(windows))]
+use winit::dpi::PhysicalPosition;
 use winit::dpi::PhysicalSize;
+#[cfg(windows)]
 pub use winit::monitor::MonitorHandle;
+#[cfg(windows)]
 use winit::platform_impl::platform;
 
+/// A monitor, as reported by SDL.
+///
+/// Winit needs its own event loop to look up monitors outside of Windows, so we mirror the parts
+/// of its monitor handle that we use.
+#[cfg(not(windows))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct MonitorHandle {
+    name: Option<String>,
+    position: PhysicalPosition<i32>,
+    size: PhysicalSize<u32>,
+    scale_factor: f64,
+}
+
+#[cfg(not(windows))]
+impl MonitorHandle {
+    fn from_display(video_subsystem: &VideoSubsystem, index: i32) -> Result<Self, String> {
+        let bounds = video_subsystem.display_bounds(index)?;
+
+        Ok(Self {
+            name: video_subsystem.display_name(index).ok(),
+            position: PhysicalPosition::new(bounds.x(), bounds.y()),
+            size: PhysicalSize::new(bounds.width(), bounds.height()),
+            scale_factor: display_scale_factor(video_subsystem, index),
+        })
+    }
+
+    pub fn name(&self) -> Option<String> {
+        self.name.clone()
+    }
+
+    pub fn position(&self) -> PhysicalPosition<i32> {
+        self.position
+    }
+
+    pub fn size(&self) -> PhysicalSize<u32> {
+        self.size
+    }
+
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+}
+
+#[cfg(not(windows))]
+fn display_scale_factor(video_subsystem: &VideoSubsystem, index: i32) -> f64 {
+    video_subsystem
+        .display_dpi(index)
+        .map_or(1.0, |(ddpi, _, _)| ddpi as f64 / 96.0)
+}
+
 pub trait HasWinitWindow {
     fn inner_size(&self) -> PhysicalSize<u32>;
     fn scale_factor(&self) -> f64;
@@ -28,6 +87,7 @@ impl HasWinitWindow for Window {
         self.subsystem().display_dpi(id).unwrap().0 as f64 / 96.0
     }
 
+    #[cfg(windows)]
     fn current_monitor(&self) -> Option<MonitorHandle> {
         match self.raw_window_handle() {
             RawWindowHandle::Win32(handle) => {
@@ -37,6 +97,12 @@ impl HasWinitWindow for Window {
             _ => None,
         }
     }
+
+    #[cfg(not(windows))]
+    fn current_monitor(&self) -> Option<MonitorHandle> {
+        let index = self.display_index().ok()?;
+        MonitorHandle::from_display(self.subsystem(), index).ok()
+    }
 }
 
 pub trait HasMonitors {
@@ -45,6 +111,7 @@ pub trait HasMonitors {
     fn available_monitors(&self) -> Self::Iter;
 }
 
+#[cfg(windows)]
 impl HasMonitors for VideoSubsystem {
     type Iter = Map<
         vec_deque::IntoIter<platform::monitor::MonitorHandle>,
@@ -58,6 +125,78 @@ impl HasMonitors for VideoSubsystem {
     }
 }
 
+#[cfg(not(windows))]
+impl HasMonitors for VideoSubsystem {
+    type Iter = std::vec::IntoIter<MonitorHandle>;
+
+    fn available_monitors(&self) -> Self::Iter {
+        let count = self.num_video_displays().unwrap_or_else(|err| {
+            log::error!("Failed to count the displays: {}", err);
+            0
+        });
+
+        (0..count)
+            .filter_map(|index| {
+                MonitorHandle::from_display(self, index)
+                    .map_err(|err| log::warn!("Failed to look up display {}: {}", index, err))
+                    .ok()
+            })
+            .collect::<Vec<MonitorHandle>>()
+            .into_iter()
+    }
+}
+
+pub trait HasStableId {
+    /// An identifier for the monitor that survives reboots.
+    ///
+    /// On Windows, it also survives changes to the monitor layout. Elsewhere, it includes the
+    /// position of the monitor, so moving the monitor around changes it.
+    fn stable_id(&self) -> String;
+}
+
+impl HasStableId for MonitorHandle {
+    #[cfg(windows)]
+    fn stable_id(&self) -> String {
+        use winit::platform::windows::MonitorHandleExtWindows;
+
+        let device_name = self.native_id();
+        crate::platform::windows::monitor::device_interface_name(&device_name)
+            .unwrap_or(device_name)
+    }
+
+    // SDL only gives us the model name, which identical monitors share, so add the position to
+    // tell them apart.
+    #[cfg(not(windows))]
+    fn stable_id(&self) -> String {
+        let position = self.position();
+        format!(
+            "{} at ({}, {})",
+            self.name().unwrap_or_default(),
+            position.x,
+            position.y
+        )
+    }
+}
+
+pub trait HasRotation {
+    /// How the monitor is rotated from its native orientation.
+    fn rotation(&self) -> Rotation;
+}
+
+impl HasRotation for MonitorHandle {
+    #[cfg(windows)]
+    fn rotation(&self) -> Rotation {
+        use winit::platform::windows::MonitorHandleExtWindows;
+
+        crate::platform::windows::monitor::rotation(&self.native_id()).unwrap_or_default()
+    }
+
+    #[cfg(not(windows))]
+    fn rotation(&self) -> Rotation {
+        Rotation::default()
+    }
+}
+
 /// [`winit::dpi::PhysicalSize<u32>`] non-zero extensions.
 pub trait NonZeroU32PhysicalSize {
     /// Converts to non-zero `(width, height)`.